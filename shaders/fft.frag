@@ -0,0 +1,9 @@
+#version 140
+out vec4 color;
+uniform vec3 colour;
+
+#include "line.glsl"
+
+void main() {
+    color = vec4(colour, line_alpha());
+}
@@ -0,0 +1,6 @@
+#version 140
+in float ampl;
+
+void main() {
+    gl_Position = vec4((gl_VertexID / float(SIZE) - 0.5) * 1.8, log2(ampl * inversesqrt(float(SIZE))) / log2(20.0) / 10, 0.0, 1.0);
+}
@@ -0,0 +1,13 @@
+// Theme gradient, interpolated in HSLuv.
+
+#include "hsluv.glsl"
+
+uniform vec3 gradient_low;
+uniform vec3 gradient_high;
+// 0 spreads the gradient over frequency, 1 over amplitude
+uniform int gradient_axis;
+
+vec3 gradient(float frequency, float amplitude) {
+    float t = clamp(gradient_axis == 0 ? frequency : amplitude, 0.0, 1.0);
+    return hsluvToRgb(mix(gradient_low, gradient_high, t));
+}
@@ -0,0 +1,10 @@
+// Coverage for lines expanded by line_geometry_shader, which passes the
+// distance from the centre of the line in pixels as `edge`.
+
+in float edge;
+
+uniform float width;
+
+float line_alpha() {
+    return clamp(width * 0.5 + 0.5 - abs(edge), 0.0, 1.0);
+}
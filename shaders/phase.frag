@@ -0,0 +1,17 @@
+#version 140
+
+in vec3 hslFrag;
+
+out vec4 color;
+
+#include "hsluv.glsl"
+#include "line.glsl"
+
+void main() {
+    color = hsluvToRgb(
+        ((hslFrag.x / 32768) + 1) * 180,
+        (0.5 - 0.5 * cos(2 * 3.141592 * hslFrag.y / 32768)) * 100,
+        (0.5 - 0.4 * cos(2 * 3.141592 * hslFrag.z / 32768)) * 100,
+        line_alpha()
+    ); //smoothstep(0, 32768, abs(hslFrag.z)) * 100
+}
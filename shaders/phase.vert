@@ -0,0 +1,12 @@
+#version 140
+in vec2 xy;
+in vec3 hsl;
+
+out vec3 hslGeo;
+
+uniform mat2 transform;
+
+void main() {
+    gl_Position = vec4(transform * (xy / 32768.0), 0.0, 1.0);
+    hslGeo = hsl;
+}
@@ -0,0 +1,15 @@
+// Example user shader. Rings pulse outwards on each beat, with the bass,
+// mid and treble levels tinting the red, green and blue channels.
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 p = (fragCoord - 0.5 * iResolution.xy) / iResolution.y;
+    float r = length(p);
+
+    // Spectrum level at this radius, from the top row of the audio texture
+    float level = texture(iChannel0, vec2(clamp(r, 0.0, 1.0), 0.25)).r;
+
+    float rings = 0.5 + 0.5 * sin(40.0 * (r - 0.3 * iBeatPhase) - 2.0 * iTime);
+    vec3 tint = vec3(iBass, iMid, iTreble);
+
+    fragColor = vec4(tint * rings * level * (0.6 + 0.4 * iBeat), 1.0);
+}
@@ -1,76 +1,123 @@
-use std::{fs::File, io::BufReader, time::Duration};
+use std::{fs::File, io::BufReader, path::{Path, PathBuf}, time::Duration};
 
 use itertools::Itertools;
 use rodio::{Decoder, OutputStream, Sink, Source};
 use ringbuf::{traits::{Consumer, RingBuffer}, HeapRb};
 
-pub struct Audio<'a> {
-    sink: Sink,
-    _stream: OutputStream,
+use crate::playlist::{Playlist, Repeat};
+
+/// Skipping back further into a track than this restarts it instead.
+const RESTART_THRESHOLD: Duration = Duration::from_secs(3);
+
+fn open(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|error| format!("Cannot play {}: {error}", path.display()))?;
+    Decoder::new(BufReader::new(file)).map_err(|error| format!("Cannot play {}: {error}", path.display()))
+}
 
-    last_sample: Duration,
-    data: Box<dyn Iterator<Item=i16> + 'a>,
+/// Opens the current track, dropping it from the playlist and moving on to
+/// the next one for as long as tracks fail to decode. `None` once the
+/// playlist runs out.
+fn open_current(playlist: &mut Playlist) -> Option<Decoder<BufReader<File>>> {
+    while !playlist.is_empty() {
+        match open(playlist.track(playlist.current())) {
+            Ok(source) => return Some(source),
+            Err(error) => {
+                eprintln!("{error}");
+                playlist.remove(playlist.current());
+            },
+        }
+    }
+    None
+}
+
+/// Second copy of the track, decoded for analysis and read in step with the
+/// playback position reported by the sink.
+struct AnalysisStream<'a> {
+    data: Box<dyn Source<Item=i16> + 'a>,
+    /// Frames taken from `data`, counted from the start of the track.
+    frames_read: u64,
+    left: HeapRb<i16>,
+    right: HeapRb<i16>,
     buffer_size: usize,
-    internal_buffer_left: HeapRb<i16>,
-    internal_buffer_right: HeapRb<i16>,
     sample_rate: usize,
     channels: usize,
+    finished: bool,
+    /// Position the sink still reports for the previous track after a
+    /// gapless switch. It is ignored until the sink catches up, as reading up
+    /// to it would decode most of the new track in one go.
+    stale: Option<Duration>,
 }
 
-impl Audio<'_> {
-    pub fn new(file_name: &str, buffer_size: usize) -> Self {
-        let file = File::open(file_name).unwrap();
-
-        let source_data = Decoder::new(BufReader::new(file)).unwrap();
-        
-        let channels = source_data.channels() as usize;
-        
-        let file2 = File::open(file_name).unwrap();        
-        let source_audio = Decoder::new(BufReader::new(file2)).unwrap();
-        let sample_rate = source_audio.sample_rate() as usize;
-        
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        
-        let sink = Sink::try_new(&stream_handle).unwrap();
-        
-        sink.pause();
-        sink.set_volume(0.5);
-        sink.append(source_audio);
-
-        Self {
-            sink,
-            _stream,
-            last_sample: Duration::ZERO,
-            data: Box::new(source_data),
+impl<'a> AnalysisStream<'a> {
+    fn new(data: Box<dyn Source<Item=i16> + 'a>, buffer_size: usize) -> Self {
+        let mut stream = Self {
+            sample_rate: (*data).sample_rate() as usize,
+            channels: (*data).channels() as usize,
+            data,
+            frames_read: 0,
+            left: HeapRb::new(buffer_size),
+            right: HeapRb::new(buffer_size),
             buffer_size,
-            internal_buffer_left: HeapRb::new(buffer_size),
-            internal_buffer_right: HeapRb::new(buffer_size),
-            sample_rate,
-            channels,
-        }
+            finished: false,
+            stale: None,
+        };
+        stream.silence();
+        stream
     }
 
-    pub fn play(&mut self) {
-        self.sink.play();
+    fn frame_at(&self, position: Duration) -> u64 {
+        (position.as_secs_f64() * self.sample_rate as f64).round() as u64
     }
 
-    pub fn get_samples(&mut self, slices: (&mut [i16], &mut[i16])) {
-        let pos = self.sink.get_pos();
-        let dif = pos - self.last_sample;
-        self.last_sample = pos;
+    /// Fills the history with zeros, so the window never holds samples from
+    /// before a jump.
+    fn silence(&mut self) {
+        self.left.push_iter_overwrite(std::iter::repeat_n(0, self.buffer_size));
+        self.right.push_iter_overwrite(std::iter::repeat_n(0, self.buffer_size));
+    }
 
-        let frames = (dif.as_secs_f32() * self.sample_rate as f32).round() as i32;
+    fn set_data(&mut self, data: Box<dyn Source<Item=i16> + 'a>) {
+        self.sample_rate = (*data).sample_rate() as usize;
+        self.channels = (*data).channels() as usize;
+        self.data = data;
+        self.finished = false;
+    }
 
-        let skip_neg = frames - self.buffer_size as i32;
-        
-        let skip = if skip_neg > 0 {skip_neg as usize} else {0};
-        let take = if skip_neg < 0 {frames as usize} else {self.buffer_size};
+    /// Continues from `position` with `data`, which must already start there.
+    fn reset(&mut self, data: Box<dyn Source<Item=i16> + 'a>, position: Duration) {
+        self.set_data(data);
+        self.frames_read = self.frame_at(position);
+        self.stale = None;
+        self.silence();
+    }
+
+    /// Carries on into `data` from its start, keeping the history, as the sink
+    /// does when one track runs into the next. `stale` is the position the
+    /// sink reports at the switch, which can still belong to the previous
+    /// track, since it is only refreshed every few milliseconds.
+    fn follow(&mut self, data: Box<dyn Source<Item=i16> + 'a>, stale: Duration) {
+        self.set_data(data);
+        self.frames_read = 0;
+        self.stale = Some(stale);
+    }
+
+    /// Reads every frame up to `position`, pushing them into the history and
+    /// `fresh`. Positions behind what has been read are ignored, since the
+    /// sink only updates its position every few milliseconds, and so is a
+    /// stale position left from the previous track.
+    fn advance_to(&mut self, position: Duration, fresh: (&mut Vec<i16>, &mut Vec<i16>)) {
+        if self.stale == Some(position) {
+            return;
+        }
+        self.stale = None;
+
+        let frames = self.frame_at(position).saturating_sub(self.frames_read) as usize;
+        let mut read = 0;
 
         let binding = self.data.by_ref()
-            .skip(skip * self.channels)
-            .take(take * self.channels)
+            .take(frames * self.channels)
             .chunks(self.channels);
-        let data = binding
+        binding
             .into_iter()
             .map(|mut chunk| {
                 let left = chunk.next().unwrap();
@@ -78,14 +125,489 @@ impl Audio<'_> {
                 (left, right)
             }).for_each(
                 |(left, right)| {
-                    self.internal_buffer_left.push_overwrite(left);
-                    self.internal_buffer_right.push_overwrite(right);
+                    self.left.push_overwrite(left);
+                    self.right.push_overwrite(right);
+                    fresh.0.push(left);
+                    fresh.1.push(right);
+                    read += 1;
                 }
             );
-        
-        
-        
-        self.internal_buffer_left.peek_slice(slices.0);
-        self.internal_buffer_right.peek_slice(slices.1);
-    }   
-}
\ No newline at end of file
+
+        self.frames_read += read as u64;
+        if read < frames {
+            self.finished = true;
+        }
+    }
+
+    fn peek(&self, slices: (&mut [i16], &mut [i16])) {
+        self.left.peek_slice(slices.0);
+        self.right.peek_slice(slices.1);
+    }
+}
+
+pub struct Audio<'a> {
+    sink: Sink,
+    _stream: OutputStream,
+    playlist: Playlist,
+    /// Track appended to the sink behind the current one, so it starts
+    /// without a gap.
+    queued: Option<usize>,
+
+    stream: AnalysisStream<'a>,
+    sample_rate: usize,
+    duration: Option<Duration>,
+}
+
+impl Audio<'_> {
+    /// Starts paused on the current track of `playlist`, failing if no track
+    /// in it can be played.
+    pub fn new(mut playlist: Playlist, buffer_size: usize) -> Result<Self, String> {
+        let source_data = open_current(&mut playlist).ok_or("Nothing to play")?;
+        let path = playlist.track(playlist.current());
+        let sample_rate = source_data.sample_rate() as usize;
+        let duration = source_data.total_duration();
+
+        let (_stream, stream_handle) = OutputStream::try_default().map_err(|error| error.to_string())?;
+
+        let sink = Sink::try_new(&stream_handle).map_err(|error| error.to_string())?;
+
+        sink.pause();
+        sink.set_volume(0.5);
+        sink.append(open(path)?);
+
+        let mut audio = Self {
+            sink,
+            _stream,
+            playlist,
+            queued: None,
+            stream: AnalysisStream::new(Box::new(source_data), buffer_size),
+            sample_rate,
+            duration,
+        };
+        audio.queue_following();
+        Ok(audio)
+    }
+
+    pub fn play(&mut self) {
+        self.sink.play();
+    }
+
+    pub fn pause(&mut self) {
+        self.sink.pause();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused() {
+            self.play();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Pauses and rewinds to the start of the track.
+    pub fn stop(&mut self) {
+        self.load(Duration::ZERO);
+    }
+
+    /// Pauses at the start of the first track.
+    pub fn restart(&mut self) {
+        if self.playlist.is_empty() {
+            return;
+        }
+        self.playlist.go_to(self.playlist.first());
+        self.load(Duration::ZERO);
+    }
+
+    /// Whether playback has reached the end of the playlist.
+    pub fn is_finished(&self) -> bool {
+        self.sink.empty() || (self.stream.finished && self.queued.is_none())
+    }
+
+    /// File of the current track, unless every track failed to play.
+    pub fn current(&self) -> Option<&Path> {
+        (!self.playlist.is_empty()).then(|| self.playlist.track(self.playlist.current()))
+    }
+
+    pub fn position(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
+    /// Length of the track, if the format records it.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Jumps to `position` in the track, clamped to its length, keeping the
+    /// analysis in step.
+    pub fn seek(&mut self, position: Duration) {
+        let position = self.duration().map_or(position, |duration| position.min(duration));
+
+        // A finished sink has nothing left to seek in
+        if self.sink.empty() {
+            let paused = self.is_paused();
+            self.load(position);
+            if !paused {
+                self.play();
+            }
+            return;
+        }
+
+        if let Err(error) = self.sink.try_seek(position) {
+            eprintln!("Cannot seek: {error}");
+            return;
+        }
+        self.resync(position);
+    }
+
+    /// Moves playback by `seconds`, backwards if negative.
+    pub fn seek_by(&mut self, seconds: f32) {
+        let target = (self.position().as_secs_f32() + seconds).max(0.);
+        self.seek(Duration::from_secs_f32(target));
+    }
+
+    /// Skips to the next track in the playlist.
+    pub fn next(&mut self) {
+        if let Some(track) = self.playlist.next() {
+            self.skip_to(track);
+        }
+    }
+
+    /// Skips to the previous track, or to the start of this one if it has
+    /// been playing for a while.
+    pub fn previous(&mut self) {
+        match self.playlist.previous() {
+            Some(track) if self.position() < RESTART_THRESHOLD => self.skip_to(track),
+            _ => self.seek(Duration::ZERO),
+        }
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.playlist.shuffle()
+    }
+
+    /// Shuffles the playlist or puts it back in order. The track already
+    /// queued behind the current one still plays next.
+    pub fn toggle_shuffle(&mut self) {
+        self.playlist.toggle_shuffle();
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.playlist.repeat()
+    }
+
+    /// Switches between repeating nothing, the playlist and the track. Like
+    /// shuffling, it applies from the track after the queued one.
+    pub fn cycle_repeat(&mut self) {
+        self.playlist.cycle_repeat();
+    }
+
+    /// Plays `paths` from the start in place of the playlist, keeping the
+    /// shuffle and repeat settings. The playlist is kept, and `false`
+    /// returned, if none of them can be played.
+    pub fn replace(&mut self, paths: Vec<PathBuf>) -> bool {
+        let mut playlist = Playlist::new(paths, self.playlist.shuffle(), self.playlist.repeat());
+        if open_current(&mut playlist).is_none() {
+            return false;
+        }
+
+        self.playlist = playlist;
+        self.load(Duration::ZERO);
+        self.play();
+        true
+    }
+
+    /// Adds `paths` to the end of the playlist, starting on them if nothing
+    /// was left to play. Returns how many tracks were added.
+    pub fn append(&mut self, paths: Vec<PathBuf>) -> usize {
+        let empty = self.playlist.is_empty();
+        let added = self.playlist.extend(paths);
+
+        if empty && !self.playlist.is_empty() {
+            self.playlist.go_to(self.playlist.first());
+            self.load(Duration::ZERO);
+            self.play();
+        } else {
+            // The current track may have been the last one
+            self.queue_following();
+        }
+        added
+    }
+
+    fn skip_to(&mut self, track: usize) {
+        let paused = self.is_paused();
+        self.playlist.go_to(track);
+        self.load(Duration::ZERO);
+        if !paused {
+            self.play();
+        }
+    }
+
+    /// Queues the current track again from `position`, paused. Tracks that
+    /// fail to decode are dropped on the way, and the sink is left empty if
+    /// none are left.
+    fn load(&mut self, position: Duration) {
+        self.queued = None;
+        let Some(source) = open_current(&mut self.playlist) else {
+            self.sink.clear();
+            return;
+        };
+        self.sample_rate = source.sample_rate() as usize;
+        self.duration = source.total_duration();
+
+        self.sink.clear();
+        self.sink.append(source);
+
+        // Also makes the sink report the new position straight away
+        if let Err(error) = self.sink.try_seek(position) {
+            eprintln!("Cannot seek: {error}");
+        }
+        self.resync(self.sink.get_pos());
+        self.queue_following();
+    }
+
+    /// Reopens the analysis stream at `position`.
+    fn resync(&mut self, position: Duration) {
+        let mut data = match open(self.playlist.track(self.playlist.current())) {
+            Ok(data) => data,
+            Err(error) => {
+                eprintln!("{error}");
+                return;
+            },
+        };
+        // Not every format can seek, so read up to the position instead, to
+        // stay in step with the sink
+        if data.try_seek(position).is_err() {
+            let frames = (position.as_secs_f64() * data.sample_rate() as f64).round() as usize;
+            let samples = frames * data.channels() as usize;
+            data.by_ref().take(samples).for_each(drop);
+        }
+        self.stream.reset(Box::new(data), position);
+    }
+
+    /// Appends the track after the current one to the sink, if there is one,
+    /// dropping any that fail to decode.
+    fn queue_following(&mut self) {
+        if self.queued.is_some() {
+            return;
+        }
+        while let Some(track) = self.playlist.following() {
+            match open(self.playlist.track(track)) {
+                Ok(source) => {
+                    self.sink.append(source);
+                    self.queued = Some(track);
+                    return;
+                },
+                Err(error) => {
+                    eprintln!("{error}");
+                    // Already playing, so it did decode a moment ago
+                    if track == self.playlist.current() {
+                        return;
+                    }
+                    self.playlist.remove(track);
+                },
+            }
+        }
+    }
+
+    /// Moves on to the queued track once the sink has started it, carrying
+    /// the analysis straight on into it.
+    fn follow_queue(&mut self) {
+        let Some(track) = self.queued else {
+            return;
+        };
+        if self.sink.len() > 1 {
+            return;
+        }
+
+        self.playlist.go_to(track);
+        self.queued = None;
+
+        match open(self.playlist.track(track)) {
+            Ok(source) => {
+                self.sample_rate = source.sample_rate() as usize;
+                self.duration = source.total_duration();
+                self.stream.follow(Box::new(source), self.sink.get_pos());
+            },
+            Err(error) => eprintln!("{error}"),
+        }
+
+        self.queue_following();
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.sink.volume()
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.sink.set_volume(volume.clamp(0., 2.));
+    }
+
+    /// Sample rate of the current track.
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Advances the analysis stream to the sink position, copying the latest
+    /// window into `slices` and every newly played frame into `fresh`.
+    pub fn get_samples(&mut self, slices: (&mut [i16], &mut[i16]), fresh: (&mut Vec<i16>, &mut Vec<i16>)) {
+        self.follow_queue();
+        self.stream.advance_to(self.sink.get_pos(), fresh);
+        self.stream.peek(slices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rodio::buffer::SamplesBuffer;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    /// Stereo stream where each frame is its index on the left and minus its
+    /// index on the right.
+    fn ramp(start: i16, end: i16) -> Box<dyn Source<Item=i16>> {
+        ramp_at(start, end, SAMPLE_RATE)
+    }
+
+    fn ramp_at(start: i16, end: i16, sample_rate: u32) -> Box<dyn Source<Item=i16>> {
+        Box::new(SamplesBuffer::new(2, sample_rate, (start..end).flat_map(|i| [i, -i]).collect::<Vec<_>>()))
+    }
+
+    fn advance(stream: &mut AnalysisStream, millis: u64) -> (Vec<i16>, Vec<i16>) {
+        let mut fresh = (Vec::new(), Vec::new());
+        stream.advance_to(Duration::from_millis(millis), (&mut fresh.0, &mut fresh.1));
+        fresh
+    }
+
+    fn window(stream: &AnalysisStream) -> (Vec<i16>, Vec<i16>) {
+        let mut window = (vec![0; stream.buffer_size], vec![0; stream.buffer_size]);
+        stream.peek((&mut window.0, &mut window.1));
+        window
+    }
+
+    #[test]
+    fn reads_frames_up_to_position() {
+        let mut stream = AnalysisStream::new(ramp(0, 100), 4);
+
+        let (left, right) = advance(&mut stream, 5);
+        assert_eq!(left, [0, 1, 2, 3, 4]);
+        assert_eq!(right, [0, -1, -2, -3, -4]);
+
+        let (left, _) = advance(&mut stream, 8);
+        assert_eq!(left, [5, 6, 7]);
+        assert_eq!(window(&stream).0, [4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn does_not_drift_over_many_short_steps() {
+        let mut stream = AnalysisStream::new(ramp_at(0, 30000, 44100), 4);
+
+        // 1/3 ms is not a whole number of frames
+        let mut total = 0;
+        for step in 1..=1500 {
+            let mut fresh = (Vec::new(), Vec::new());
+            stream.advance_to(Duration::from_secs_f64(step as f64 / 3000.), (&mut fresh.0, &mut fresh.1));
+            total += fresh.0.len();
+        }
+        assert_eq!(total, 22050);
+    }
+
+    #[test]
+    fn ignores_positions_behind_the_stream() {
+        let mut stream = AnalysisStream::new(ramp(0, 100), 4);
+        advance(&mut stream, 10);
+
+        let (left, _) = advance(&mut stream, 3);
+        assert!(left.is_empty());
+
+        let (left, _) = advance(&mut stream, 12);
+        assert_eq!(left, [10, 11]);
+    }
+
+    #[test]
+    fn starts_silent() {
+        let stream = AnalysisStream::new(ramp(0, 100), 4);
+        assert_eq!(window(&stream), (vec![0; 4], vec![0; 4]));
+    }
+
+    #[test]
+    fn reset_clears_history_and_follows_new_position() {
+        let mut stream = AnalysisStream::new(ramp(0, 100), 4);
+        advance(&mut stream, 50);
+
+        // Seek back to 20 ms, with the decoder already there
+        stream.reset(ramp(20, 100), Duration::from_millis(20));
+        assert_eq!(window(&stream).0, [0, 0, 0, 0]);
+
+        let (left, _) = advance(&mut stream, 23);
+        assert_eq!(left, [20, 21, 22]);
+        assert_eq!(window(&stream).0, [0, 20, 21, 22]);
+    }
+
+    #[test]
+    fn reset_forwards_skips_intermediate_frames() {
+        let mut stream = AnalysisStream::new(ramp(0, 100), 4);
+        advance(&mut stream, 5);
+
+        stream.reset(ramp(60, 100), Duration::from_millis(60));
+        let (left, _) = advance(&mut stream, 62);
+        assert_eq!(left, [60, 61]);
+    }
+
+    #[test]
+    fn finishes_when_data_runs_out() {
+        let mut stream = AnalysisStream::new(ramp(0, 10), 4);
+
+        advance(&mut stream, 10);
+        assert!(!stream.finished);
+
+        let (left, _) = advance(&mut stream, 15);
+        assert!(left.is_empty());
+        assert!(stream.finished);
+
+        stream.reset(ramp(0, 10), Duration::ZERO);
+        assert!(!stream.finished);
+    }
+
+    #[test]
+    fn mono_is_copied_to_both_channels() {
+        let mut stream = AnalysisStream::new(Box::new(SamplesBuffer::new(1, SAMPLE_RATE, (0..10).collect::<Vec<i16>>())), 4);
+
+        let (left, right) = advance(&mut stream, 3);
+        assert_eq!(left, [0, 1, 2]);
+        assert_eq!(right, [0, 1, 2]);
+    }
+
+    #[test]
+    fn follow_carries_on_into_the_next_track() {
+        let mut stream = AnalysisStream::new(ramp(0, 10), 4);
+        advance(&mut stream, 10);
+
+        // The sink starts the next track from zero, at its own sample rate
+        stream.follow(ramp_at(100, 200, 2000), Duration::from_millis(10));
+        assert_eq!(window(&stream).0, [6, 7, 8, 9]);
+
+        let (left, _) = advance(&mut stream, 1);
+        assert_eq!(left, [100, 101]);
+        assert_eq!(window(&stream).0, [8, 9, 100, 101]);
+    }
+
+    #[test]
+    fn follow_waits_for_the_sink_to_leave_the_previous_track() {
+        let mut stream = AnalysisStream::new(ramp(0, 10), 4);
+        advance(&mut stream, 10);
+
+        // The sink has not yet refreshed its position for the new track
+        stream.follow(ramp(100, 200), Duration::from_millis(10));
+        assert!(advance(&mut stream, 10).0.is_empty());
+        assert!(advance(&mut stream, 10).0.is_empty());
+
+        let (left, _) = advance(&mut stream, 2);
+        assert_eq!(left, [100, 101]);
+    }
+}
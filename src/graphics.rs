@@ -1,7 +1,7 @@
-use std::time::{Duration, Instant};
+use std::{collections::HashMap, path::{Path, PathBuf}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
 use crate::{
-    audio, processing::{self, ProcessorOutput}
+    audio, metadata::{Metadata, PendingMetadata}, playlist::{Playlist, Repeat}, processing::{self, ProcessorOutput}
 };
 
 use glium::{winit::{self, window::Window}, Surface};
@@ -14,18 +14,22 @@ pub struct WindowSettings {
     width: u32,
     height: u32,
 
+    /// Samples per pixel for multisample anti-aliasing, 0 to disable.
+    samples: u8,
+
     last_refresh: Instant,
     frametime: Duration,
 }
 
 impl WindowSettings {
-    pub fn new(title: &str, width: u32, height:u32, max_framerate: f32) -> Self {
+    pub fn new(title: &str, width: u32, height:u32, max_framerate: f32, samples: u8) -> Self {
         let frametime = Duration::from_secs_f32(1.0 / max_framerate);
 
         Self {
             title: title.to_string(),
             width,
             height,
+            samples,
 
             last_refresh: Instant::now(),
             frametime,
@@ -33,80 +37,512 @@ impl WindowSettings {
     }
 }
 
+/// How long action feedback stays on screen.
+const NOTICE_TIME: Duration = Duration::from_secs(3);
+/// How long errors stay on screen.
+const WARNING_TIME: Duration = Duration::from_secs(10);
+/// Lines of an error shown on screen. The rest only goes to stderr.
+const WARNING_LINES: usize = 3;
+
+/// Name of the theme taken from the cover art.
+const ART_THEME: &str = "cover art";
+
 pub struct Renderer {
     window: Window,
     display: Display,
-    
-    left_fft: programs::fftprogram::FFTProgram,
-    right_fft: programs::fftprogram::FFTProgram,
-    left_phase: programs::phaseprogram::PhaseProgram,
+    registry: Registry,
+    settings: ProgramSettings,
+    visualisations: HashMap<String, Box<dyn Visualisation>>,
+    layout: Layout,
+    watcher: Option<watcher::ShaderWatcher>,
+    /// Index of the active theme in `settings.themes`.
+    theme: usize,
+    post: Option<programs::postprogram::PostProgram>,
+    track: Option<Metadata>,
+    text: programs::textprogram::TextProgram,
+    /// Status lines in the top left corner.
+    hud: Vec<String>,
+    /// Key list shown over everything, while open.
+    help: Option<Vec<String>>,
+    /// Feedback on the last action or error, shown under the status lines
+    /// until the given time.
+    notice: Option<(String, Instant)>,
+    /// Mouse position in window pixels from the bottom left, while it is
+    /// over the window.
+    cursor: Option<[f32; 2]>,
 }
 
 impl Renderer {
-    pub fn new(display: &Display, window: Window, fft_bins: usize, phase_len: usize) -> Self {
-        Self {
+    pub fn new(
+        display: &Display,
+        window: Window,
+        mut settings: ProgramSettings,
+        layout: Layout,
+        processor: &processing::Processor,
+    ) -> Self {
+        let size = window.inner_size();
+
+        if settings.themes.is_empty() {
+            settings.themes.push(Theme::default());
+        }
+
+        // Built-in shaders plus the directories holding user shaders
+        let mut directories = vec![Path::new(programs::SHADER_DIR)];
+        for shader in &settings.shaders {
+            let directory = shader.path.parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+        let watcher = watcher::ShaderWatcher::new(directories)
+            .inspect_err(|error| eprintln!("Shader hot reloading disabled: {error}"))
+            .ok();
+
+        let post = (!settings.post_process.is_empty())
+            .then(|| programs::postprogram::PostProgram::new(display, settings.post_process.clone()));
+
+        let mut renderer = Self {
             window,
             display: display.clone(),
-            
-            left_fft: programs::fftprogram::FFTProgram::new(fft_bins, &display,[0.0, 0.0, 0.0]),
-            right_fft: programs::fftprogram::FFTProgram::new(fft_bins, &display, [1.0, 0.0, 0.0]),
-            left_phase: programs::phaseprogram::PhaseProgram::new(phase_len, &display, [0.0, 0.0, 0.0]),
+            registry: Registry::new(&settings),
+            settings,
+            visualisations: HashMap::new(),
+            layout,
+            watcher,
+            theme: 0,
+            post,
+            track: None,
+            text: programs::textprogram::TextProgram::new(display),
+            hud: Vec::new(),
+            help: None,
+            notice: None,
+            cursor: None,
+        };
+        renderer.layout.resize(size.width, size.height);
+        renderer.build_missing(processor);
+        renderer.arranged();
+        renderer
+    }
+
+    /// Builds any visualisation named in the layout that does not exist yet.
+    fn build_missing(&mut self, processor: &processing::Processor) {
+        let mut failed = Vec::new();
+        for (name, _) in self.layout.viewports() {
+            if self.visualisations.contains_key(name) {
+                continue;
+            }
+            match self.registry.build(name, &self.display, &self.settings, processor) {
+                Ok(mut visualisation) => {
+                    visualisation.set_theme(&self.settings.themes[self.theme]);
+                    if let Some(track) = &self.track {
+                        visualisation.set_track(track);
+                    }
+                    self.visualisations.insert(name.clone(), visualisation);
+                },
+                Err(error) => failed.push(format!("Cannot show {name}: {error}")),
+            }
+        }
+        for error in failed {
+            self.warn(error);
+        }
+    }
+
+    /// Moves every visualisation on to a processor with a new sample rate.
+    pub fn set_sample_rate(&mut self, processor: &processing::Processor) {
+        for visualisation in self.visualisations.values_mut() {
+            visualisation.set_sample_rate(processor.sample_rate(), processor.bin_width());
+        }
+    }
+
+    /// Lets every visualisation know its viewport after the layout changed.
+    fn arranged(&mut self) {
+        for (name, viewport) in self.layout.viewports() {
+            if let Some(visualisation) = self.visualisations.get_mut(name) {
+                visualisation.resize(*viewport);
+            }
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.display.resize((width, height));
+        self.layout.resize(width, height);
+        self.arranged();
+    }
+
+    /// Shows or hides the `index`th registered visualisation.
+    pub fn toggle_panel(&mut self, index: usize, processor: &processing::Processor) {
+        let Some(name) = self.registry.names().nth(index) else {
+            return;
+        };
+        self.layout.toggle(&name.to_string());
+        self.build_missing(processor);
+        self.arranged();
+    }
+
+    /// Grows (`factor` > 1) or shrinks the panel under a point in window
+    /// coordinates.
+    pub fn resize_panel_at(&mut self, x: f64, y: f64, factor: f32) {
+        if let Some(panel) = self.layout.panel_at(x, y).map(str::to_string) {
+            self.layout.resize_panel(&panel, factor);
+            self.arranged();
+        }
+    }
+
+    /// Switches the panel under a point in window coordinates to its next mode.
+    pub fn next_mode_at(&mut self, x: f64, y: f64) {
+        if let Some(visualisation) = self.layout.panel_at(x, y).and_then(|panel| self.visualisations.get_mut(panel)) {
+            visualisation.next_mode();
+        }
+    }
+
+    pub fn toggle_fullscreen(&self) {
+        let fullscreen = match self.window.fullscreen() {
+            Some(_) => None,
+            None => Some(winit::window::Fullscreen::Borderless(None)),
+        };
+        self.window.set_fullscreen(fullscreen);
+    }
+
+    /// Saves the last presented frame as a PNG in the working directory.
+    pub fn screenshot(&mut self) {
+        let frame: glium::texture::RawImage2d<u8> = match self.display.read_front_buffer() {
+            Ok(frame) => frame,
+            Err(error) => {
+                eprintln!("Screenshot failed: {error:?}");
+                self.notify("Screenshot failed".to_string());
+                return;
+            },
+        };
+        let Some(image) = image::RgbaImage::from_raw(frame.width, frame.height, frame.data.into_owned()) else {
+            return;
+        };
+
+        // OpenGL rows start at the bottom
+        let image = image::imageops::flip_vertical(&image);
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let name = format!("screenshot-{seconds}.png");
+        match image.save(&name) {
+            Ok(()) => self.notify(format!("Saved {name}")),
+            Err(error) => {
+                eprintln!("Screenshot failed: {error}");
+                self.notify("Screenshot failed".to_string());
+            },
+        }
+    }
+
+    /// Switches every visualisation to the theme at `index`.
+    fn set_theme(&mut self, index: usize) {
+        self.theme = index;
+        let theme = &self.settings.themes[self.theme];
+        for visualisation in self.visualisations.values_mut() {
+            visualisation.set_theme(theme);
+        }
+    }
+
+    pub fn next_theme(&mut self) {
+        self.set_theme((self.theme + 1) % self.settings.themes.len());
+        self.notify(format!("Theme: {}", self.settings.themes[self.theme].name));
+    }
+
+    /// Switches the spectrum gridlines to the next frequency grid.
+    pub fn cycle_grid(&mut self) {
+        self.settings.grid.frequencies = FrequencyGrid::cycle(self.settings.grid.frequencies);
+        for visualisation in self.visualisations.values_mut() {
+            visualisation.set_grid(self.settings.grid);
+        }
+    }
+
+    /// Shows the tags of the track at `path` in the window title and on every
+    /// visualisation, and switches to its cover art theme if enabled.
+    pub fn set_track(&mut self, path: &Path, track: Metadata) {
+        let title = track.display_title(path);
+        self.window.set_title(&title);
+        self.notify(format!("Playing {title}"));
+
+        for visualisation in self.visualisations.values_mut() {
+            visualisation.set_track(&track);
+        }
+
+        if self.settings.art_theme {
+            // Only ever one, kept at the end so other indices stay put
+            let showing = self.settings.themes[self.theme].name == ART_THEME;
+            self.settings.themes.retain(|theme| theme.name != ART_THEME);
+
+            let art_theme = track.art.as_ref().and_then(|art| Theme::from_picture(ART_THEME, &art.pixels));
+            match art_theme {
+                Some(theme) => {
+                    self.settings.themes.push(theme);
+                    self.set_theme(self.settings.themes.len() - 1);
+                },
+                None if showing => self.set_theme(0),
+                None => (),
+            }
+        }
+
+        self.track = Some(track);
+    }
+
+    /// Replaces the status lines, shown if `settings.hud` is set.
+    pub fn set_hud(&mut self, lines: Vec<String>) {
+        self.hud = lines;
+    }
+
+    /// Shows `message` under the status lines for `NOTICE_TIME`, whether or
+    /// not they are on.
+    pub fn notify(&mut self, message: String) {
+        self.notice = Some((message, Instant::now() + NOTICE_TIME));
+    }
+
+    /// Reports an error on stderr, and its first few lines on screen for
+    /// `WARNING_TIME`.
+    fn warn(&mut self, message: String) {
+        eprintln!("{message}");
+        let lines = message.lines().take(WARNING_LINES).collect::<Vec<_>>().join("\n");
+        self.notice = Some((lines, Instant::now() + WARNING_TIME));
+    }
+
+    /// Opens the help overlay listing `lines`, or closes it.
+    pub fn toggle_help(&mut self, lines: Vec<String>) {
+        self.help = match self.help {
+            Some(_) => None,
+            None => Some(lines),
+        };
+    }
+
+    /// Moves the mouse readout to a point in window coordinates, or hides it.
+    pub fn hover(&mut self, cursor: Option<(f64, f64)>) {
+        let height = self.window.inner_size().height as f64;
+        self.cursor = cursor.map(|(x, y)| [x as f32, (height - y) as f32]);
+    }
+
+    /// Queues the status lines and help overlay over the whole window.
+    fn overlays(&mut self, (width, height): (u32, u32)) {
+        let size = self.settings.text_size;
+        let [r, g, b] = self.settings.themes[self.theme].text;
+        let margin = size * 0.5;
+
+        let mut lines = if self.settings.hud { self.hud.clone() } else { Vec::new() };
+        self.notice = self.notice.take().filter(|(_, until)| Instant::now() < *until);
+        if let Some((notice, _)) = &self.notice {
+            lines.push(notice.clone());
+        }
+
+        if !lines.is_empty() {
+            let hud = lines.join("\n");
+            let [w, h] = self.text.measure(&hud, size);
+            let top = height as f32 - margin;
+            self.text.rectangle([0., top - h - margin], [w + 2. * margin, height as f32], [0., 0., 0., 0.4]);
+            self.text.text(&hud, [margin, top], size, [r, g, b, 1.], [0., 1.]);
+        }
+
+        if let Some(help) = &self.help {
+            let help = help.join("\n");
+            let [w, h] = self.text.measure(&help, size);
+            let centre = [width as f32 / 2., height as f32 / 2.];
+            self.text.rectangle(
+                [centre[0] - w / 2. - size, centre[1] - h / 2. - size],
+                [centre[0] + w / 2. + size, centre[1] + h / 2. + size],
+                [0., 0., 0., 0.75],
+            );
+            self.text.text(&help, centre, size, [r, g, b, 1.], [0.5, 0.5]);
+        }
+    }
+
+    /// Rebuilds the shaders of every visualisation whose files changed.
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+
+        let mut errors = Vec::new();
+        for (name, visualisation) in self.visualisations.iter_mut() {
+            if let Err(error) = visualisation.reload(&changed) {
+                errors.push(format!("Keeping previous {name} shader: {error}"));
+            }
+        }
+        for error in errors {
+            self.warn(error);
         }
     }
 
     pub fn render(&mut self, values: &ProcessorOutput) {
+        self.reload_shaders();
+
         let mut target = self.display.draw();
-        target.clear_color(0., 0., 0., 1.);
+        let [r, g, b] = self.settings.themes[self.theme].background;
+        target.clear_color_and_depth((r, g, b, 1.), 1.);
 
-        self.left_fft.render(&mut target, &values.left_fft);
-        self.right_fft.render(&mut target, &values.right_fft);
+        for visualisation in self.visualisations.values_mut() {
+            visualisation.update(values);
+        }
 
-        self.left_phase.render(&mut target, &values.phase_left);
+        for (name, viewport) in self.layout.viewports() {
+            if let Some(visualisation) = self.visualisations.get_mut(name) {
+                visualisation.draw(&mut target, *viewport);
+            }
+        }
+
+        if let Some(post) = self.post.as_mut() {
+            post.render(&mut target, values.beat);
+        }
+
+        // Text goes on last, so post-processing leaves it readable
+        for (name, viewport) in self.layout.viewports() {
+            if let Some(visualisation) = self.visualisations.get(name) {
+                let cursor = self.cursor.filter(|&[x, y]| {
+                    x >= viewport.left as f32 && x < (viewport.left + viewport.width) as f32
+                        && y >= viewport.bottom as f32 && y < (viewport.bottom + viewport.height) as f32
+                });
+                visualisation.label(&mut self.text, *viewport, cursor);
+            }
+        }
+        self.overlays(target.get_dimensions());
+        self.text.draw(&mut target);
 
         target.finish().unwrap();
     }
+
+    pub fn camera(&mut self) -> Option<&mut camera::OrbitCamera> {
+        self.visualisations.values_mut().find_map(|visualisation| visualisation.camera())
+    }
+
+    /// Camera of the panel under a point in window coordinates.
+    pub fn camera_at(&mut self, x: f64, y: f64) -> Option<&mut camera::OrbitCamera> {
+        let panel = self.layout.panel_at(x, y)?;
+        self.visualisations.get_mut(panel)?.camera()
+    }
 }
 
+mod camera;
+mod keymap;
+mod layout;
 mod programs;
+mod theme;
+mod visualisation;
+mod watcher;
+
+pub use keymap::{Action, KeyMap};
+pub use layout::{Direction, Layout, Node};
+pub use theme::{Gradient, GradientAxis, Theme};
+pub use visualisation::{ProgramSettings, Registry, Visualisation};
+
+pub use programs::meterprogram::MeterStyle;
+pub use programs::phaseprogram::{PhaseGain, PhaseMode, PhaseSettings};
+pub use programs::spectrogramprogram::{ColourMap, ScrollDirection, SpectrogramSettings};
+pub use programs::barsprogram::BarSettings;
+pub use programs::radialprogram::RadialSettings;
+pub use programs::terrainprogram::TerrainSettings;
+pub use programs::scopeprogram::{ScopeMode, ScopeStyle};
+pub use programs::shaderprogram::ShaderSettings;
+pub use programs::postprogram::{Effect, Param};
+pub use programs::artprogram::ArtSettings;
+pub use programs::fftprogram::{FrequencyGrid, GridSettings};
 
 pub struct App<'a> {
     window_settings: WindowSettings,
+    program_settings: ProgramSettings,
+    layout: Option<Layout>,
     audio: audio::Audio<'a>,
     processor: processing::Processor,
     renderer: Option<Renderer>,
-    counter: usize,
+    keymap: KeyMap,
+    /// Smoothed frames per second and milliseconds spent per frame.
+    frame_rate: f32,
+    frame_time: f32,
+    last_frame: Instant,
+    cursor: Option<(f64, f64)>,
+    dragging: bool,
+    modifiers: winit::keyboard::ModifiersState,
+    /// Files dropped on the window since the last frame.
+    dropped: Vec<PathBuf>,
+    /// Track whose tags are on show, or being read.
+    track: Option<PathBuf>,
+    pending: Option<PendingMetadata>,
 }
 
+/// Weight of the newest frame in the smoothed frame rate and time.
+const FRAME_SMOOTHING: f32 = 0.05;
+
+fn smooth(average: f32, value: f32) -> f32 {
+    if average == 0. {
+        value
+    } else {
+        average + (value - average) * FRAME_SMOOTHING
+    }
+}
+
+/// Jump made by the seek keys.
+const SEEK_STEP: f32 = 5.;
+/// Volume change per key press or scroll step, where 1 is full volume.
+const VOLUME_STEP: f32 = 0.05;
+
 impl App<'_> {
     pub fn new(
         title: &str, 
         width: u32, 
         height: u32, 
         max_framerate: f32, 
-        audio_file: &str, 
+        samples: u8,
+        playlist: Playlist,
         sample_window: usize, 
         fft_output_bins: usize,
         phase_pts: usize,
-    ) -> Self {
-        let window_settings= WindowSettings::new(title, width, height, max_framerate);
-        let audio = audio::Audio::new(audio_file, sample_window);
-        let processor = processing::Processor::new(sample_window, fft_output_bins, phase_pts);
+        peak_settings: processing::peak::PeakSettings,
+        meter_type: processing::meter::MeterType,
+        correlation_time: Duration,
+        mid_side: bool,
+        scope_settings: Option<processing::scope::ScopeSettings>,
+        program_settings: ProgramSettings,
+        layout: Layout,
+        keymap: KeyMap,
+    ) -> Result<Self, String> {
+        let window_settings= WindowSettings::new(title, width, height, max_framerate, samples);
+        let audio = audio::Audio::new(playlist, sample_window)?;
+        let processor = processing::Processor::new(
+            sample_window,
+            fft_output_bins,
+            phase_pts,
+            audio.sample_rate(),
+            peak_settings,
+            meter_type,
+            correlation_time,
+            mid_side,
+            scope_settings,
+        );
         
-        Self {
+        Ok(Self {
             window_settings,
+            program_settings,
+            layout: Some(layout),
             audio,
             processor,
             renderer: None,
-            counter: 0,
-        }
+            keymap,
+            frame_rate: 0.,
+            frame_time: 0.,
+            last_frame: Instant::now(),
+            cursor: None,
+            dragging: false,
+            modifiers: Default::default(),
+            dropped: Vec::new(),
+            track: None,
+            pending: None,
+        })
     }
 
     fn start(&mut self, display: &Display, window: Window) {
         let render_data = Renderer::new(
             &display,
             window,
-            self.processor.fft_output_bins,
-            self.processor.phase_pts,
+            self.program_settings.clone(),
+            self.layout.take().unwrap(),
+            &self.processor,
         );
         
         self.renderer = Some(render_data);
@@ -114,18 +550,145 @@ impl App<'_> {
     }
 
     fn render(&mut self) {
-        self.audio.get_samples((&mut self.processor.audio_buffer.0, &mut self.processor.audio_buffer.1));
+        // Several files dropped together arrive one event each
+        if !self.dropped.is_empty() {
+            let dropped = std::mem::take(&mut self.dropped);
+            let renderer = self.renderer.as_mut().unwrap();
+            if self.modifiers.shift_key() {
+                let added = self.audio.append(dropped);
+                renderer.notify(format!("Queued {added} tracks"));
+            } else if !self.audio.replace(dropped) {
+                renderer.notify("Nothing to play".to_string());
+            }
+        }
+
+        if self.audio.is_finished() {
+            self.audio.restart();
+        }
+
+        self.audio.get_samples(
+            (&mut self.processor.audio_buffer.0, &mut self.processor.audio_buffer.1),
+            (&mut self.processor.new_samples.0, &mut self.processor.new_samples.1),
+        );
+
+        if self.audio.sample_rate() != self.processor.sample_rate() {
+            self.processor.reset(self.audio.sample_rate());
+            self.renderer.as_mut().unwrap().set_sample_rate(&self.processor);
+        }
+
+        if let Some(current) = self.audio.current() {
+            if self.track.as_deref() != Some(current) {
+                self.pending = Some(PendingMetadata::read(current));
+                self.track = Some(current.to_path_buf());
+            }
+        }
+        if let Some(pending) = &self.pending {
+            if let Some(track) = pending.try_take() {
+                self.renderer.as_mut().unwrap().set_track(pending.path(), track);
+                self.pending = None;
+            }
+        }
+
+        let started = Instant::now();
+        let interval = started.duration_since(self.last_frame).as_secs_f32();
+        self.frame_rate = smooth(self.frame_rate, 1. / interval.max(1e-6));
+        self.last_frame = started;
 
         let bars = self.processor.process_samples();
-        self.renderer.as_mut().unwrap().render(&bars);
+        let hud = self.hud();
+        let renderer = self.renderer.as_mut().unwrap();
+        renderer.set_hud(hud);
+        renderer.render(&bars);
+        self.frame_time = smooth(self.frame_time, started.elapsed().as_secs_f32() * 1000.);
+    }
+
+    /// Track, position and frame rate, then how to get help.
+    fn hud(&self) -> Vec<String> {
+        let clock = |time: Duration| {
+            let seconds = time.as_secs();
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        };
+        let title = self.renderer.as_ref()
+            .and_then(|renderer| renderer.track.as_ref())
+            .zip(self.audio.current())
+            .map(|(track, path)| track.display_title(path))
+            .unwrap_or_default();
+        let position = match self.audio.duration() {
+            Some(duration) => format!("{} / {}", clock(self.audio.position()), clock(duration)),
+            None => clock(self.audio.position()),
+        };
+        let paused = if self.audio.is_paused() { "  paused" } else { "" };
+
+        let mut lines = vec![
+            title,
+            format!("{position}{paused}"),
+            format!("{:.0} fps  {:.1} ms", self.frame_rate, self.frame_time),
+        ];
+        if let Some(key) = self.keymap.key_for(Action::Help) {
+            lines.push(format!("{key} for help"));
+        }
+        lines
+    }
+
+    fn perform(&mut self, action: Action) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        let cursor = self.cursor;
+
+        match action {
+            Action::TogglePlay => self.audio.toggle_pause(),
+            Action::Stop => self.audio.stop(),
+            Action::SeekBack => self.audio.seek_by(-SEEK_STEP),
+            Action::SeekForward => self.audio.seek_by(SEEK_STEP),
+            Action::NextTrack => self.audio.next(),
+            Action::PreviousTrack => self.audio.previous(),
+            Action::ToggleShuffle => {
+                self.audio.toggle_shuffle();
+                renderer.notify(format!("Shuffle {}", if self.audio.shuffle() { "on" } else { "off" }));
+            },
+            Action::CycleRepeat => {
+                self.audio.cycle_repeat();
+                renderer.notify(match self.audio.repeat() {
+                    Repeat::Off => "Repeat off",
+                    Repeat::All => "Repeat playlist",
+                    Repeat::One => "Repeat track",
+                }.to_string());
+            },
+            Action::VolumeDown => self.audio.set_volume(self.audio.volume() - VOLUME_STEP),
+            Action::VolumeUp => self.audio.set_volume(self.audio.volume() + VOLUME_STEP),
+            Action::NextTheme => renderer.next_theme(),
+            Action::CycleGrid => renderer.cycle_grid(),
+            Action::CycleMode => if let Some((x, y)) = cursor {
+                renderer.next_mode_at(x, y);
+            },
+            Action::TogglePanel(index) => renderer.toggle_panel(index, &self.processor),
+            Action::GrowPanel | Action::ShrinkPanel => if let Some((x, y)) = cursor {
+                let factor = if action == Action::GrowPanel { 1.25 } else { 0.8 };
+                renderer.resize_panel_at(x, y, factor);
+            },
+            Action::Camera(key) => if let Some(camera) = renderer.camera() {
+                camera.key(key);
+            },
+            Action::Fullscreen => renderer.toggle_fullscreen(),
+            Action::Screenshot => renderer.screenshot(),
+            Action::Help => renderer.toggle_help(self.keymap.help()),
+        }
     }
 }
 
 impl winit::application::ApplicationHandler for App<'_> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let mut config = glium::glutin::config::ConfigTemplateBuilder::new().with_depth_size(24);
+        if self.window_settings.samples > 0 {
+            config = config.with_multisampling(self.window_settings.samples);
+        }
+
         let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
             .with_title(&self.window_settings.title)
-            .with_inner_size(self.window_settings.width, self.window_settings.height).build(event_loop);
+            .with_inner_size(self.window_settings.width, self.window_settings.height)
+            .with_config_template_builder(config)
+            .build(event_loop);
 
         self.start(&display, window);
     }
@@ -138,21 +701,62 @@ impl winit::application::ApplicationHandler for App<'_> {
     ) {
         match event {
             winit::event::WindowEvent::CloseRequested => event_loop.exit(),
+            winit::event::WindowEvent::Resized(size) => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.resize(size.width, size.height);
+                }
+            },
             winit::event::WindowEvent::RedrawRequested => {
                 self.render();
                 let elapsed = Instant::now().duration_since(self.window_settings.last_refresh);
-                self.counter += 1;
-                if self.counter > 30 {
-                    println!("{:#?}", 1. / (elapsed).as_secs_f32());
-                    self.counter = 0;
-                }
                 let delay = self.window_settings.frametime.saturating_sub(elapsed);
                 std::thread::sleep(delay);
                 self.window_settings.last_refresh = Instant::now();
 
                 self.renderer.as_ref().unwrap().window.request_redraw();
             },
+            winit::event::WindowEvent::MouseInput { state, button: winit::event::MouseButton::Left, .. } => {
+                self.dragging = state.is_pressed();
+            },
+            winit::event::WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some((x, y))) = (self.dragging, self.cursor) {
+                    if let Some(camera) = self.renderer.as_mut().and_then(|r| r.camera_at(x, y)) {
+                        camera.drag((position.x - x) as f32, (position.y - y) as f32);
+                    }
+                }
+                self.cursor = Some((position.x, position.y));
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.hover(self.cursor);
+                }
+            },
+            winit::event::WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.hover(None);
+                }
+            },
+            winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+                    winit::event::MouseScrollDelta::PixelDelta(p) => p.y as f32 / 50.,
+                };
+
+                // Zoom 3D panels, and change the volume anywhere else
+                let (x, y) = self.cursor.unwrap_or_default();
+                match self.renderer.as_mut().and_then(|r| r.camera_at(x, y)) {
+                    Some(camera) => camera.zoom(steps),
+                    None => self.audio.set_volume(self.audio.volume() + steps * VOLUME_STEP),
+                }
+            },
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            // Plays dropped files and folders, or queues them with shift held
+            winit::event::WindowEvent::DroppedFile(path) => self.dropped.push(path),
+            winit::event::WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => {
+                if let Some(action) = self.keymap.action(&event.logical_key) {
+                    self.perform(action);
+                }
+            },
             _ => ()
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,139 @@
+pub type Mat4 = [[f32; 4]; 4];
+
+const PITCH_LIMIT: f32 = 1.5;
+const DISTANCE_RANGE: (f32, f32) = (0.5, 20.);
+
+/// Radians of rotation per pixel of mouse drag.
+const DRAG_SPEED: f32 = 0.005;
+/// Radians of rotation per key press.
+const KEY_STEP: f32 = 0.05;
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = dot(a, a).sqrt();
+    [a[0] / len, a[1] / len, a[2] / len]
+}
+
+/// Column-major product `a * b`.
+pub fn multiply(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut out = [[0.; 4]; 4];
+    for (c, column) in out.iter_mut().enumerate() {
+        for (r, value) in column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    out
+}
+
+pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let f = 1. / (fov_y / 2.).tan();
+    [
+        [f / aspect, 0., 0., 0.],
+        [0., f, 0., 0.],
+        [0., 0., (far + near) / (near - far), -1.],
+        [0., 0., 2. * far * near / (near - far), 0.],
+    ]
+}
+
+pub fn look_at(eye: [f32; 3], target: [f32; 3], up: [f32; 3]) -> Mat4 {
+    let f = normalize(sub(target, eye));
+    let s = normalize(cross(f, up));
+    let u = cross(s, f);
+    [
+        [s[0], u[0], -f[0], 0.],
+        [s[1], u[1], -f[1], 0.],
+        [s[2], u[2], -f[2], 0.],
+        [-dot(s, eye), -dot(u, eye), dot(f, eye), 1.],
+    ]
+}
+
+/// Camera orbiting a target point, driven by mouse drags, the scroll wheel
+/// and the arrow keys.
+#[derive(Clone, Copy, Debug)]
+pub struct OrbitCamera {
+    pub target: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub fov_y: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: [0., 0.2, 0.],
+            yaw: 0.,
+            pitch: 0.5,
+            distance: 3.,
+            fov_y: std::f32::consts::FRAC_PI_4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraKey {
+    Left,
+    Right,
+    Up,
+    Down,
+    ZoomIn,
+    ZoomOut,
+}
+
+impl OrbitCamera {
+    pub fn eye(&self) -> [f32; 3] {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        [
+            self.target[0] + self.distance * sy * cp,
+            self.target[1] + self.distance * sp,
+            self.target[2] + self.distance * cy * cp,
+        ]
+    }
+
+    pub fn view_projection(&self, aspect: f32) -> Mat4 {
+        let view = look_at(self.eye(), self.target, [0., 1., 0.]);
+        let projection = perspective(self.fov_y, aspect, 0.05, 100.);
+        multiply(&projection, &view)
+    }
+
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+    }
+
+    /// Positive steps move towards the target.
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance = (self.distance * 0.9f32.powf(steps)).clamp(DISTANCE_RANGE.0, DISTANCE_RANGE.1);
+    }
+
+    pub fn drag(&mut self, dx: f32, dy: f32) {
+        self.rotate(-dx * DRAG_SPEED, dy * DRAG_SPEED);
+    }
+
+    pub fn key(&mut self, key: CameraKey) {
+        match key {
+            CameraKey::Left => self.rotate(-KEY_STEP, 0.),
+            CameraKey::Right => self.rotate(KEY_STEP, 0.),
+            CameraKey::Up => self.rotate(0., KEY_STEP),
+            CameraKey::Down => self.rotate(0., -KEY_STEP),
+            CameraKey::ZoomIn => self.zoom(1.),
+            CameraKey::ZoomOut => self.zoom(-1.),
+        }
+    }
+}
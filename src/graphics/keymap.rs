@@ -0,0 +1,155 @@
+use glium::winit::keyboard::{Key, NamedKey};
+
+use crate::graphics::camera::CameraKey;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    TogglePlay,
+    Stop,
+    SeekBack,
+    SeekForward,
+    NextTrack,
+    PreviousTrack,
+    ToggleShuffle,
+    CycleRepeat,
+    VolumeDown,
+    VolumeUp,
+    NextTheme,
+    /// Switches the spectrum gridlines between off, octaves and decades.
+    CycleGrid,
+    /// Switches the panel under the cursor to its next display mode.
+    CycleMode,
+    /// Shows or hides the `n`th registered visualisation.
+    TogglePanel(usize),
+    /// Grows the panel under the cursor.
+    GrowPanel,
+    ShrinkPanel,
+    Camera(CameraKey),
+    Fullscreen,
+    Screenshot,
+    Help,
+}
+
+impl Action {
+    pub fn description(&self) -> String {
+        match self {
+            Action::TogglePlay => "play/pause".to_string(),
+            Action::Stop => "stop".to_string(),
+            Action::SeekBack => "seek back".to_string(),
+            Action::SeekForward => "seek forward".to_string(),
+            Action::NextTrack => "next track".to_string(),
+            Action::PreviousTrack => "previous track".to_string(),
+            Action::ToggleShuffle => "shuffle on/off".to_string(),
+            Action::CycleRepeat => "repeat off/playlist/track".to_string(),
+            Action::VolumeDown => "volume down".to_string(),
+            Action::VolumeUp => "volume up".to_string(),
+            Action::NextTheme => "next theme".to_string(),
+            Action::CycleGrid => "frequency grid off/octaves/decades".to_string(),
+            Action::CycleMode => "next mode of the panel under the cursor".to_string(),
+            Action::TogglePanel(index) => format!("show/hide panel {}", index + 1),
+            Action::GrowPanel => "grow the panel under the cursor".to_string(),
+            Action::ShrinkPanel => "shrink the panel under the cursor".to_string(),
+            Action::Camera(key) => format!("camera {}", format!("{key:?}").to_lowercase()),
+            Action::Fullscreen => "fullscreen".to_string(),
+            Action::Screenshot => "screenshot".to_string(),
+            Action::Help => "show/hide this help".to_string(),
+        }
+    }
+}
+
+/// Readable name of a key, for the help.
+pub fn key_name(key: &Key) -> String {
+    match key {
+        Key::Character(c) if c.as_str() == " " => "Space".to_string(),
+        Key::Character(c) => c.to_uppercase(),
+        Key::Named(named) => format!("{named:?}"),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Keys bound to actions, in the order the help lists them. A key can only
+/// be bound to one action, but an action can have several keys.
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let character = |c: &str| Key::Character(c.into());
+
+        let mut bindings = vec![
+            (Key::Named(NamedKey::Space), Action::TogglePlay),
+            (character("x"), Action::Stop),
+            (character(","), Action::SeekBack),
+            (character("."), Action::SeekForward),
+            (character("n"), Action::NextTrack),
+            (character("b"), Action::PreviousTrack),
+            (character("z"), Action::ToggleShuffle),
+            (character("r"), Action::CycleRepeat),
+            (character("["), Action::VolumeDown),
+            (character("]"), Action::VolumeUp),
+            (character("t"), Action::NextTheme),
+            (character("g"), Action::CycleGrid),
+            (character("m"), Action::CycleMode),
+            (character("+"), Action::GrowPanel),
+            (character("="), Action::GrowPanel),
+            (character("-"), Action::ShrinkPanel),
+            (Key::Named(NamedKey::ArrowLeft), Action::Camera(CameraKey::Left)),
+            (Key::Named(NamedKey::ArrowRight), Action::Camera(CameraKey::Right)),
+            (Key::Named(NamedKey::ArrowUp), Action::Camera(CameraKey::Up)),
+            (Key::Named(NamedKey::ArrowDown), Action::Camera(CameraKey::Down)),
+            (Key::Named(NamedKey::PageUp), Action::Camera(CameraKey::ZoomIn)),
+            (Key::Named(NamedKey::PageDown), Action::Camera(CameraKey::ZoomOut)),
+            (character("f"), Action::Fullscreen),
+            (character("p"), Action::Screenshot),
+            (character("h"), Action::Help),
+        ];
+
+        let panels = [
+            NamedKey::F1, NamedKey::F2, NamedKey::F3, NamedKey::F4, NamedKey::F5,
+            NamedKey::F6, NamedKey::F7, NamedKey::F8, NamedKey::F9, NamedKey::F10,
+            NamedKey::F11, NamedKey::F12,
+        ];
+        bindings.extend(panels.into_iter().enumerate().map(|(i, key)| (Key::Named(key), Action::TogglePanel(i))));
+
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Binds `key` to `action`, replacing whatever it was bound to.
+    pub fn bind(&mut self, key: Key, action: Action) {
+        self.bindings.retain(|(bound, _)| *bound != key);
+        self.bindings.push((key, action));
+    }
+
+    /// Action bound to `key`. Letters match either case.
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        let key = match key {
+            Key::Character(c) => Key::Character(c.to_lowercase().into()),
+            key => key.clone(),
+        };
+        self.bindings.iter().find(|(bound, _)| *bound == key).map(|(_, action)| *action)
+    }
+
+    /// Name of the first key bound to `action`.
+    pub fn key_for(&self, action: Action) -> Option<String> {
+        self.bindings.iter().find(|(_, bound)| *bound == action).map(|(key, _)| key_name(key))
+    }
+
+    /// One line per action, listing its keys.
+    pub fn help(&self) -> Vec<String> {
+        let mut lines: Vec<(Action, Vec<String>)> = Vec::new();
+        for (key, action) in &self.bindings {
+            match lines.iter_mut().find(|(existing, _)| existing == action) {
+                Some((_, keys)) => keys.push(key_name(key)),
+                None => lines.push((*action, vec![key_name(key)])),
+            }
+        }
+
+        lines.into_iter()
+            .map(|(action, keys)| format!("{:<16} {}", keys.join(" "), action.description()))
+            .collect()
+    }
+}
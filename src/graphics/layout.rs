@@ -0,0 +1,333 @@
+use glium::Rect;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+    /// Children side by side, first on the left.
+    Horizontal,
+    /// Children stacked vertically, first at the top.
+    Vertical,
+}
+
+#[derive(Clone, Debug)]
+pub enum Node {
+    /// A visualisation, by its name in the registry.
+    Panel(String),
+    /// Children drawn over each other in the same rectangle, in order.
+    Stack(Vec<Node>),
+    /// Children sharing the rectangle in proportion to their weights.
+    Split(Direction, Vec<(f32, Node)>),
+}
+
+/// Smallest weight a panel can be shrunk to, relative to its siblings.
+const MIN_WEIGHT: f32 = 0.05;
+
+impl Node {
+    fn contains(&self, panel: &str) -> bool {
+        match self {
+            Node::Panel(p) => p == panel,
+            Node::Stack(children) => children.iter().any(|child| child.contains(panel)),
+            Node::Split(_, children) => children.iter().any(|(_, child)| child.contains(panel)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Node::Panel(_) => false,
+            Node::Stack(children) => children.is_empty(),
+            Node::Split(_, children) => children.is_empty(),
+        }
+    }
+
+    /// Removes every occurrence of `panel`, dropping containers left empty.
+    fn remove(&mut self, panel: &str) {
+        match self {
+            Node::Panel(_) => (),
+            Node::Stack(children) => {
+                children.retain(|child| !matches!(child, Node::Panel(p) if p == panel));
+                children.iter_mut().for_each(|child| child.remove(panel));
+                children.retain(|child| !child.is_empty());
+            },
+            Node::Split(_, children) => {
+                children.retain(|(_, child)| !matches!(child, Node::Panel(p) if p == panel));
+                children.iter_mut().for_each(|(_, child)| child.remove(panel));
+                children.retain(|(_, child)| !child.is_empty());
+            },
+        }
+    }
+
+    /// Scales the weight of the split child holding `panel`, starting from the
+    /// innermost split. Returns false if the panel is not in a split.
+    fn resize(&mut self, panel: &str, factor: f32) -> bool {
+        match self {
+            Node::Panel(_) => false,
+            Node::Stack(children) => children.iter_mut().any(|child| child.resize(panel, factor)),
+            Node::Split(_, children) => {
+                let Some((weight, child)) = children.iter_mut().find(|(_, child)| child.contains(panel)) else {
+                    return false;
+                };
+                if !child.resize(panel, factor) {
+                    *weight = (*weight * factor).max(MIN_WEIGHT);
+                }
+                true
+            },
+        }
+    }
+
+    fn arrange(&self, rect: Rect, margin: u32, out: &mut Vec<(String, Rect)>) {
+        match self {
+            Node::Panel(panel) => out.push((panel.clone(), inset(rect, margin))),
+            Node::Stack(children) => children.iter().for_each(|child| child.arrange(rect, margin, out)),
+            Node::Split(direction, children) => {
+                let total: f32 = children.iter().map(|(weight, _)| weight).sum();
+                let length = match direction {
+                    Direction::Horizontal => rect.width,
+                    Direction::Vertical => rect.height,
+                };
+
+                // Round the running edge rather than each share, so the
+                // children always tile the whole rectangle
+                let mut start = 0;
+                let mut covered = 0.;
+                for (weight, child) in children {
+                    covered += weight;
+                    let end = (covered / total * length as f32).round() as u32;
+                    let part = match direction {
+                        Direction::Horizontal => Rect { left: rect.left + start, width: end - start, ..rect },
+                        Direction::Vertical => Rect {
+                            bottom: rect.bottom + rect.height - end,
+                            height: end - start,
+                            ..rect
+                        },
+                    };
+                    child.arrange(part, margin, out);
+                    start = end;
+                }
+            },
+        }
+    }
+}
+
+fn inset(rect: Rect, margin: u32) -> Rect {
+    let x = margin.min(rect.width / 2);
+    let y = margin.min(rect.height / 2);
+    Rect {
+        left: rect.left + x,
+        bottom: rect.bottom + y,
+        width: rect.width - 2 * x,
+        height: rect.height - 2 * y,
+    }
+}
+
+/// Split tree assigning each panel a viewport. Viewports are recomputed
+/// whenever the tree or the window size changes.
+pub struct Layout {
+    root: Node,
+    /// Gap left around each panel, in pixels.
+    margin: u32,
+    size: (u32, u32),
+    viewports: Vec<(String, Rect)>,
+}
+
+impl Layout {
+    pub fn new(root: Node, margin: u32) -> Self {
+        Self {
+            root,
+            margin,
+            size: (0, 0),
+            viewports: Vec::new(),
+        }
+    }
+
+    /// Panels in draw order, with their viewports in framebuffer pixels.
+    pub fn viewports(&self) -> &[(String, Rect)] {
+        &self.viewports
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+        self.arrange();
+    }
+
+    fn arrange(&mut self) {
+        let (width, height) = self.size;
+        self.viewports.clear();
+        self.root.arrange(Rect { left: 0, bottom: 0, width, height }, self.margin, &mut self.viewports);
+    }
+
+    pub fn contains(&self, panel: &str) -> bool {
+        self.root.contains(panel)
+    }
+
+    /// Adds a panel after everything else in the top level split.
+    pub fn add(&mut self, panel: &str) {
+        let panel = Node::Panel(panel.to_string());
+        match &mut self.root {
+            Node::Split(_, children) => children.push((1., panel)),
+            root => {
+                let old = std::mem::replace(root, Node::Stack(Vec::new()));
+                *root = if old.is_empty() {
+                    panel
+                } else {
+                    Node::Split(Direction::Horizontal, vec![(1., old), (1., panel)])
+                };
+            },
+        }
+        self.arrange();
+    }
+
+    pub fn remove(&mut self, panel: &str) {
+        if matches!(&self.root, Node::Panel(p) if p == panel) {
+            self.root = Node::Stack(Vec::new());
+        }
+        self.root.remove(panel);
+        self.arrange();
+    }
+
+    pub fn toggle(&mut self, panel: &str) {
+        if self.contains(panel) {
+            self.remove(panel);
+        } else {
+            self.add(panel);
+        }
+    }
+
+    /// Grows (`factor` > 1) or shrinks the space given to a panel.
+    pub fn resize_panel(&mut self, panel: &str, factor: f32) {
+        if self.root.resize(panel, factor) {
+            self.arrange();
+        }
+    }
+
+    /// Topmost panel under a point in window coordinates, measured from the
+    /// top left like cursor positions.
+    pub fn panel_at(&self, x: f64, y: f64) -> Option<&str> {
+        let (x, y) = (x as u32, self.size.1.saturating_sub(y as u32));
+        self.viewports.iter().rev()
+            .find(|(_, r)| x >= r.left && x < r.left + r.width && y >= r.bottom && y < r.bottom + r.height)
+            .map(|(panel, _)| panel.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panel(name: &str) -> Node {
+        Node::Panel(name.to_string())
+    }
+
+    /// `a` on the left, with `b`, `c` and `d` stacked on the right.
+    fn layout(margin: u32) -> Layout {
+        let column = Node::Split(Direction::Vertical, vec![(1., panel("b")), (1., panel("c")), (1., panel("d"))]);
+        let mut layout = Layout::new(Node::Split(Direction::Horizontal, vec![(1., panel("a")), (1., column)]), margin);
+        layout.resize(1001, 301);
+        layout
+    }
+
+    fn viewport(layout: &Layout, panel: &str) -> Rect {
+        layout.viewports().iter().find(|(name, _)| name == panel).unwrap().1
+    }
+
+    /// Checks that `parts` fill `parent` side by side from left to right.
+    fn assert_tiles_across(parent: Rect, parts: &[Rect]) {
+        let mut left = parent.left;
+        for part in parts {
+            assert_eq!((part.left, part.bottom, part.height), (left, parent.bottom, parent.height), "{parts:?}");
+            left += part.width;
+        }
+        assert_eq!(left, parent.left + parent.width, "{parts:?}");
+    }
+
+    /// Checks that `parts` fill `parent` one above the other from the top.
+    fn assert_tiles_down(parent: Rect, parts: &[Rect]) {
+        let mut top = parent.bottom + parent.height;
+        for part in parts {
+            assert_eq!((part.bottom + part.height, part.left, part.width), (top, parent.left, parent.width), "{parts:?}");
+            top -= part.height;
+        }
+        assert_eq!(top, parent.bottom, "{parts:?}");
+    }
+
+    /// Checks that `a` and the `column` panels fill the whole window.
+    fn assert_tiled(layout: &Layout, column: &[&str]) {
+        let a = viewport(layout, "a");
+        let column = column.iter().map(|panel| viewport(layout, panel)).collect::<Vec<_>>();
+        let right = Rect { left: a.width, bottom: 0, width: 1001 - a.width, height: 301 };
+        assert_tiles_across(Rect { left: 0, bottom: 0, width: 1001, height: 301 }, &[a, right]);
+        assert_tiles_down(right, &column);
+    }
+
+    #[test]
+    fn split_rounds_running_edges() {
+        let layout = layout(0);
+        assert_tiled(&layout, &["b", "c", "d"]);
+
+        // 100.3 and 200.7 round to 100 and 201
+        assert_eq!(viewport(&layout, "a").width, 501);
+        assert_eq!(["b", "c", "d"].map(|panel| viewport(&layout, panel).height), [100, 101, 100]);
+    }
+
+    #[test]
+    fn margin_insets_each_panel() {
+        let layout = layout(4);
+        assert_eq!(viewport(&layout, "a"), Rect { left: 4, bottom: 4, width: 493, height: 293 });
+        assert_eq!(viewport(&layout, "b"), Rect { left: 505, bottom: 205, width: 492, height: 92 });
+    }
+
+    #[test]
+    fn resized_children_still_tile() {
+        let mut layout = layout(0);
+        layout.resize_panel("c", 1.7);
+        assert_tiled(&layout, &["b", "c", "d"]);
+        assert!(viewport(&layout, "c").height > viewport(&layout, "b").height);
+
+        // Nothing shrinks away completely
+        layout.resize_panel("a", 0.);
+        assert_tiled(&layout, &["b", "c", "d"]);
+        assert!(viewport(&layout, "a").width > 0);
+    }
+
+    #[test]
+    fn removed_panels_leave_no_gap() {
+        let mut layout = layout(0);
+        layout.remove("c");
+        assert!(!layout.contains("c"));
+        assert_tiled(&layout, &["b", "d"]);
+
+        layout.remove("b");
+        layout.remove("d");
+        assert_eq!(layout.viewports().len(), 1);
+        assert_eq!(viewport(&layout, "a"), Rect { left: 0, bottom: 0, width: 1001, height: 301 });
+    }
+
+    #[test]
+    fn toggle_adds_to_the_top_level_split() {
+        let mut layout = layout(0);
+        layout.toggle("b");
+        assert!(!layout.contains("b"));
+
+        layout.toggle("b");
+        let panels = layout.viewports().iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(panels, ["a", "c", "d", "b"]);
+        let c = viewport(&layout, "c");
+        let column = Rect { left: c.left, bottom: 0, width: c.width, height: 301 };
+        assert_tiles_down(column, &[c, viewport(&layout, "d")]);
+        assert_tiles_across(
+            Rect { left: 0, bottom: 0, width: 1001, height: 301 },
+            &[viewport(&layout, "a"), column, viewport(&layout, "b")],
+        );
+    }
+
+    #[test]
+    fn finds_the_topmost_panel_from_the_top_left() {
+        let mut layout = Layout::new(Node::Split(Direction::Horizontal, vec![
+            (1., panel("a")),
+            (1., Node::Stack(vec![panel("under"), panel("over")])),
+        ]), 0);
+        layout.resize(100, 50);
+
+        assert_eq!(layout.panel_at(10., 5.), Some("a"));
+        assert_eq!(layout.panel_at(60., 45.), Some("over"));
+        assert_eq!(layout.panel_at(100., 10.), None);
+    }
+}
@@ -1,9 +1,68 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, path::{Path, PathBuf}, time::{Duration, Instant}};
 
-use glium::{Frame, Program, Surface, VertexBuffer};
-use crate::graphics::Display;
+use glium::{implement_vertex, Program, Rect, Surface, VertexBuffer};
+use crate::{graphics::Display, processing::FrequencyScale};
+
+/// Directory holding the built-in shaders, watched for hot reloading.
+pub const SHADER_DIR: &str = "shaders";
+
+/// Shader stage kept in `SHADER_DIR`, with a copy compiled into the binary.
+/// Create with `shader_file!`.
+#[derive(Clone, Copy)]
+pub struct ShaderFile {
+    name: &'static str,
+    embedded: &'static str,
+}
+
+impl ShaderFile {
+    pub fn path(&self) -> PathBuf {
+        Path::new(SHADER_DIR).join(self.name)
+    }
+
+    /// Reads the file from disk, so edits are picked up, or falls back to the
+    /// embedded copy when running away from the source tree.
+    pub fn read(&self) -> String {
+        std::fs::read_to_string(self.path()).unwrap_or_else(|_| self.embedded.to_string())
+    }
+
+    /// Whether this file is one of the `changed` paths.
+    fn changed(&self, changed: &[PathBuf]) -> bool {
+        changed.iter().any(|path| path.ends_with(self.path()))
+    }
+}
+
+macro_rules! shader_file {
+    ($name:literal) => {
+        $crate::graphics::programs::ShaderFile {
+            name: $name,
+            embedded: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/", $name)),
+        }
+    };
+}
+
+/// Chunks any shader can pull in with `#include "name"`. The chunk at index
+/// `i` is compiled as source string `i + 1`, so compile errors inside it can
+/// be traced back to it.
+const LIBRARY: &[ShaderFile] = &[
+    shader_file!("hsluv.glsl"),
+    shader_file!("line.glsl"),
+    shader_file!("gradient.glsl"),
+];
+
+pub mod artprogram;
 pub mod fftprogram;
 pub mod phaseprogram;
+pub mod peakprogram;
+pub mod meterprogram;
+pub mod correlationprogram;
+pub mod spectrogramprogram;
+pub mod barsprogram;
+pub mod radialprogram;
+pub mod terrainprogram;
+pub mod scopeprogram;
+pub mod shaderprogram;
+pub mod postprogram;
+pub mod textprogram;
 
 #[derive(Clone)]
 pub struct ShaderSrc {
@@ -13,14 +72,340 @@ pub struct ShaderSrc {
 }
 
 impl ShaderSrc {
-    pub fn get_program(&self, display: &glium::Display<glium::glutin::surface::WindowSurface>) -> glium::Program {
+    /// Adds `#define name value` to every stage, straight after `#version`.
+    pub fn define(mut self, name: &str, value: impl std::fmt::Display) -> Self {
+        let directive = format!("#define {name} {value}");
+        for source in [&mut self.vertex_shader, &mut self.fragment_shader].into_iter().chain(self.geometry_shader.as_mut()) {
+            *source = insert_after_version(source, &directive);
+        }
+        self
+    }
+
+    /// Expands `#include`s and compiles the program.
+    pub fn compile(&self, display: &glium::Display<glium::glutin::surface::WindowSurface>) -> Result<glium::Program, ShaderError> {
+        let expanded = ShaderSrc {
+            vertex_shader: expand_includes(&self.vertex_shader),
+            fragment_shader: expand_includes(&self.fragment_shader),
+            geometry_shader: self.geometry_shader.as_deref().map(expand_includes),
+        };
+
         Program::from_source(display, 
-            &self.vertex_shader, 
-            &self.fragment_shader, 
-            self.geometry_shader.as_ref().map(|x| x.as_str())
-        ).unwrap()
+            &expanded.vertex_shader, 
+            &expanded.fragment_shader, 
+            expanded.geometry_shader.as_ref().map(|x| x.as_str())
+        ).map_err(|error| ShaderError::new(error, &expanded))
+    }
+
+    /// Compiles shaders that ship with the program, where failing is a bug.
+    pub fn get_program(&self, display: &glium::Display<glium::glutin::surface::WindowSurface>) -> glium::Program {
+        self.compile(display).unwrap_or_else(|error| panic!("{error}"))
+    }
+}
+
+/// Inserts a line after `#version`, which has to stay first, then resets the
+/// line count so the following lines keep their numbers.
+fn insert_after_version(source: &str, directive: &str) -> String {
+    let mut out = String::with_capacity(source.len() + directive.len() + 16);
+    let mut inserted = false;
+
+    for (i, line) in source.lines().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+        if !inserted && line.trim_start().starts_with("#version") {
+            out.push_str(&format!("{directive}\n#line {}\n", i + 2));
+            inserted = true;
+        }
+    }
+
+    if inserted {
+        out
+    } else {
+        format!("{directive}\n#line 1\n{out}")
+    }
+}
+
+/// Replaces `#include "name"` lines with chunks from `LIBRARY`, wrapped in
+/// `#line` directives. Each chunk is included at most once, and unknown names
+/// become `#error`s so the compiler reports them at the include.
+fn expand_includes(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    expand_into(source, 0, &mut Vec::new(), &mut out);
+    out
+}
+
+fn expand_into(source: &str, string: usize, included: &mut Vec<usize>, out: &mut String) {
+    let mut number = 1;
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+
+        if let Some(next) = trimmed.strip_prefix("#line").and_then(|rest| rest.split_whitespace().next()?.parse().ok()) {
+            out.push_str(line);
+            out.push('\n');
+            number = next;
+            continue;
+        }
+
+        match trimmed.strip_prefix("#include") {
+            Some(name) => {
+                let name = name.trim().trim_matches('"');
+                match LIBRARY.iter().position(|file| file.name == name) {
+                    // Keep the line so the numbering still holds
+                    Some(index) if included.contains(&index) => out.push('\n'),
+                    Some(index) => {
+                        included.push(index);
+                        out.push_str(&format!("#line 1 {}\n", index + 1));
+                        expand_into(&LIBRARY[index].read(), index + 1, included, out);
+                        out.push_str(&format!("#line {} {string}\n", number + 1));
+                    },
+                    None => out.push_str(&format!("#error unknown include {name}\n")),
+                }
+            },
+            None => {
+                out.push_str(line);
+                out.push('\n');
+            },
+        }
+        number += 1;
+    }
+}
+
+/// Shader compile or link failure, with the driver's log and the source lines
+/// it refers to.
+#[derive(Debug)]
+pub struct ShaderError(String);
+
+impl ShaderError {
+    fn new(error: glium::ProgramCreationError, shaders: &ShaderSrc) -> Self {
+        use glium::program::ShaderType;
+
+        let glium::ProgramCreationError::CompilationError(log, stage) = &error else {
+            return Self(error.to_string());
+        };
+        let source = match stage {
+            ShaderType::Vertex => &shaders.vertex_shader,
+            ShaderType::Fragment => &shaders.fragment_shader,
+            ShaderType::Geometry => shaders.geometry_shader.as_deref().unwrap_or_default(),
+            _ => "",
+        };
+
+        let mut message = format!("{stage:?} shader failed to compile:");
+        for entry in log.lines().filter(|entry| !entry.trim().is_empty()) {
+            message.push_str(&format!("\n{entry}"));
+            let Some((string, number)) = log_location(entry) else {
+                continue;
+            };
+            if let Some(line) = source_line(source, string, number) {
+                let location = match string.checked_sub(1).and_then(|i| LIBRARY.get(i)) {
+                    Some(file) => format!("{}:{number}", file.name),
+                    None => number.to_string(),
+                };
+                message.push_str(&format!("\n    {location:>4} | {}", line.trim_end()));
+            }
+        }
+        Self(message)
+    }
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Source string and line number from a driver log entry. Drivers write
+/// either `0:12(5):` (Mesa), `ERROR: 0:12:` or `0(12) :` (NVIDIA), where the
+/// first number is the source string.
+fn log_location(entry: &str) -> Option<(usize, usize)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+    entry.char_indices()
+        .filter(|&(i, c)| c.is_ascii_digit() && !entry[..i].ends_with(|c: char| c.is_ascii_digit()))
+        .find_map(|(i, _)| {
+            let rest = &entry[i..];
+            let (string, rest) = rest.split_at(digits(rest));
+            let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+            let line = &rest[..digits(rest)];
+            Some((string.parse().ok()?, line.parse().ok()?))
+        })
+}
+
+/// Line in source string `string` as numbered by the compiler, following
+/// `#line` directives.
+fn source_line(source: &str, string: usize, number: usize) -> Option<&str> {
+    let mut current = (0, 1);
+    for line in source.lines() {
+        if let Some(next) = line.trim().strip_prefix("#line") {
+            let mut parts = next.split_whitespace();
+            let line = parts.next()?.parse().ok()?;
+            let string = match parts.next() {
+                Some(string) => string.parse().ok()?,
+                None => current.0,
+            };
+            current = (string, line);
+            continue;
+        }
+        if current == (string, number) {
+            return Some(line);
+        }
+        current.1 += 1;
     }
+    None
+}
+
+/// Bottom of the dB scale used when drawing spectra.
+pub const SPECTRUM_MIN_DB: f32 = -100.;
+
+/// Maps an FFT magnitude onto 0..1 over the `SPECTRUM_MIN_DB`..0 dB range,
+/// relative to the magnitude of a full scale sine.
+pub fn spectrum_level(magnitude: f32, reference: f32) -> f32 {
+    let db = 20. * (magnitude / reference).max(f32::MIN_POSITIVE).log10();
+    (1. - db / SPECTRUM_MIN_DB).clamp(0., 1.)
+}
+
+/// FFT magnitude of a full scale sine, after the Hann window.
+pub fn spectrum_reference(fft_window: usize) -> f32 {
+    32768. * fft_window as f32 / 2.
+}
+
+/// Resamples FFT bins onto `output` along a frequency scale, as 0..1 levels.
+pub fn resample_spectrum(values: &[f32], output: &mut [f32], scale: FrequencyScale, nyquist: f32, bin_width: f32, reference: f32) {
+    let len = output.len() as f32;
+
+    for (i, level) in output.iter_mut().enumerate() {
+        let frequency = scale.frequency((i as f32 + 0.5) / len, nyquist);
+        let bin = ((frequency / bin_width) as usize).min(values.len() - 1);
+        *level = spectrum_level(values[bin], reference);
+    }
+}
+
+/// Counts how many history steps have elapsed, so scrolling displays cover
+/// the same time span at any frame rate.
+pub struct ScrollClock {
+    steps_per_second: f32,
+    pending: f32,
+    last_tick: Instant,
+}
+
+impl ScrollClock {
+    pub fn new(steps: usize, time_span: Duration) -> Self {
+        Self {
+            steps_per_second: steps as f32 / time_span.as_secs_f32(),
+            pending: 0.,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn tick(&mut self) -> usize {
+        let now = Instant::now();
+        self.pending += now.duration_since(self.last_tick).as_secs_f32() * self.steps_per_second;
+        self.last_tick = now;
+
+        let steps = self.pending.floor();
+        self.pending -= steps;
+        steps as usize
+    }
+}
+
+/// Geometry shader expanding a `LineStripAdjacency` strip into mitred quads
+/// `width` pixels wide, plus a pixel either side for anti-aliasing. Passes
+/// `vec3 {varying}Geo` from the vertex shader through to `{varying}Frag`, if
+/// given, and `edge` is the distance from the centre of the line in pixels.
+/// Fragment shaders get their alpha from `line_alpha()` in `line.glsl`. Draw
+/// with `line_strip_indices`, so the first and last segments are kept.
+pub fn line_geometry_shader(varying: Option<&str>) -> String {
+    let declarations = varying
+        .map(|varying| format!("in vec3 {varying}Geo[];\n        out vec3 {varying}Frag;"))
+        .unwrap_or_default();
+    let pass = |vertex: usize| varying
+        .map(|varying| format!("{varying}Frag = {varying}Geo[{vertex}];"))
+        .unwrap_or_default();
+    let (pass1, pass2) = (pass(1), pass(2));
+
+    format!(r#"
+        #version 330 core
+        {declarations}
+        out float edge;
+
+        layout(lines_adjacency) in;
+        layout(triangle_strip, max_vertices=4) out;
+
+        uniform float width;
+        uniform vec2 viewport_size;
+
+        vec2 perp(vec2 v) {{
+            return vec2(-v.y, v.x);
+        }}
+
+        // Clip space to pixels, so the line is equally thick in any direction
+        vec2 pixels(vec4 p) {{
+            return p.xy / p.w * viewport_size * 0.5;
+        }}
+
+        // Carries on along `fallback` where the strip repeats its end point
+        vec2 direction(vec4 from, vec4 to, vec2 fallback) {{
+            vec2 d = pixels(to) - pixels(from);
+            return length(d) > 0.0 ? normalize(d) : fallback;
+        }}
+
+        void main() {{
+            // Nothing to draw between two points in the same place, and no
+            // direction to draw it in
+            vec2 segment = pixels(gl_in[2].gl_Position) - pixels(gl_in[1].gl_Position);
+            if (length(segment) == 0.0) {{
+                return;
+            }}
+            vec2 dir2 = normalize(segment);
+            vec2 dir1 = direction(gl_in[0].gl_Position, gl_in[1].gl_Position, dir2);
+            vec2 dir3 = direction(gl_in[2].gl_Position, gl_in[3].gl_Position, dir2);
+
+            vec2 normal = perp(dir2);
+            float extent = width * 0.5 + 1.0;
+
+            // Compute the miter vectors at both ends of the segment, back in clip space
+            vec2 miter1 = perp(normalize(dir1 + dir2));
+            float scale1 = clamp(extent / dot(miter1, normal), -extent * 4, extent * 4);
+            vec2 offset1 = miter1 * scale1 / (viewport_size * 0.5);
+            vec2 miter2 = perp(normalize(dir2 + dir3));
+            float scale2 = clamp(extent / dot(miter2, normal), -extent * 4, extent * 4);
+            vec2 offset2 = miter2 * scale2 / (viewport_size * 0.5);
+
+            // Emit vertices for the miter joint
+            {pass1}
+            edge = extent;
+            gl_Position = gl_in[1].gl_Position + vec4(offset1, 0.0, 0.0);
+            EmitVertex();
+
+            {pass1}
+            edge = -extent;
+            gl_Position = gl_in[1].gl_Position - vec4(offset1, 0.0, 0.0);
+            EmitVertex();
+
+            {pass2}
+            edge = extent;
+            gl_Position = gl_in[2].gl_Position + vec4(offset2, 0.0, 0.0);
+            EmitVertex();
+
+            {pass2}
+            edge = -extent;
+            gl_Position = gl_in[2].gl_Position - vec4(offset2, 0.0, 0.0);
+            EmitVertex();
+
+            EndPrimitive();
+        }}
+    "#)
 }
+
+/// Indices drawing `len` vertices as a `LineStripAdjacency` strip with each
+/// end repeated, as every segment needs a vertex either side of it.
+pub fn line_strip_indices(display: &Display, len: usize) -> glium::IndexBuffer<u32> {
+    let indices = std::iter::once(0)
+        .chain(0..len as u32)
+        .chain(std::iter::once(len.saturating_sub(1) as u32))
+        .collect::<Vec<_>>();
+    glium::IndexBuffer::new(display, glium::index::PrimitiveType::LineStripAdjacency, &indices).unwrap()
+}
+
 pub trait Decay<T> {
     fn assign(&mut self, rhs: T);
 }
@@ -33,10 +418,18 @@ where
     program: Program,
     vertex_pre_buffer: Vec<V>,
     vertex_buffer: VertexBuffer<V>,
+    draw_parameters: glium::DrawParameters<'static>,
+    reload: Option<Reload>,
 
     _phantom: PhantomData<X>,
 }
 
+/// Shader files a program was built from, and how to rebuild its sources.
+struct Reload {
+    files: Vec<ShaderFile>,
+    build: Box<dyn Fn() -> ShaderSrc>,
+}
+
 impl<X, V> ProgramRunner<X, V>
 where 
     X: Default + Clone + Copy + std::fmt::Debug, 
@@ -48,12 +441,41 @@ where
             program: shaders.get_program(display),
             vertex_pre_buffer: vec![V::default(); size],
             vertex_buffer: VertexBuffer::empty_dynamic(display, size).unwrap(),
+            draw_parameters: Default::default(),
+            reload: None,
 
             _phantom: PhantomData,
         }
     }
+
+    /// Like `new`, but rebuilds the program with `build` when `reload` is
+    /// given one of `files`.
+    fn reloadable<F: Fn() -> ShaderSrc + 'static>(size: usize, display: &Display, files: &[ShaderFile], build: F) -> Self {
+        let mut runner = Self::new(size, display, build());
+        runner.reload = Some(Reload { files: files.to_vec(), build: Box::new(build) });
+        runner
+    }
+
+    /// Recompiles the program if any of `changed` is one of its files. The
+    /// old program is kept if the new sources fail to compile.
+    pub fn reload(&mut self, display: &Display, changed: &[PathBuf]) -> Result<(), ShaderError> {
+        let Some(reload) = &self.reload else {
+            return Ok(());
+        };
+        if !reload.files.iter().chain(LIBRARY).any(|file| file.changed(changed)) {
+            return Ok(());
+        }
+
+        self.program = (reload.build)().compile(display)?;
+        Ok(())
+    }
+
+    /// Replaces the default parameters, e.g. to enable depth testing or blending.
+    pub fn set_draw_parameters(&mut self, draw_parameters: glium::DrawParameters<'static>) {
+        self.draw_parameters = draw_parameters;
+    }
     
-    pub fn render<'a, I: Into<glium::index::IndicesSource<'a>>, U:glium::uniforms::Uniforms>(&mut self, target: &mut Frame, values: &[X], indices: I, uniforms: &U) {
+    pub fn render<'a, S: Surface, I: Into<glium::index::IndicesSource<'a>>, U:glium::uniforms::Uniforms>(&mut self, target: &mut S, viewport: Rect, values: &[X], indices: I, uniforms: &U) {
         self.vertex_pre_buffer.iter_mut().zip(values).for_each(|(v, x)| v.assign(*x));
 
         self.vertex_buffer.write(&self.vertex_pre_buffer);
@@ -62,8 +484,142 @@ where
             &self.vertex_buffer, 
             indices, 
             &self.program, 
-            uniforms, 
-            &Default::default()
+            &WithViewport { uniforms, size: [viewport.width as f32, viewport.height as f32] },
+            &glium::DrawParameters { viewport: Some(viewport), ..self.draw_parameters.clone() }
+        ).unwrap();
+    }
+}
+
+/// A program's own uniforms plus `viewport_size`, the viewport in pixels.
+struct WithViewport<'a, U> {
+    uniforms: &'a U,
+    size: [f32; 2],
+}
+
+impl<U: glium::uniforms::Uniforms> glium::uniforms::Uniforms for WithViewport<'_, U> {
+    fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
+        self.uniforms.visit_values(&mut f);
+        f("viewport_size", glium::uniforms::UniformValue::Vec2(self.size));
+    }
+}
+
+#[derive(Copy, Clone)]
+struct QuadVertex {
+    pos: [f32; 2],
+}
+implement_vertex!(QuadVertex, pos);
+
+/// Two triangles covering the whole viewport, for full-screen passes. The
+/// vertex shader receives `pos` in clip space.
+pub struct ScreenQuad {
+    vertex_buffer: VertexBuffer<QuadVertex>,
+}
+
+impl ScreenQuad {
+    pub const VERTEX_SHADER: &'static str = r#"
+        #version 140
+        in vec2 pos;
+
+        out vec2 uv;
+
+        void main() {
+            gl_Position = vec4(pos, 0.0, 1.0);
+            uv = pos * 0.5 + 0.5;
+        }
+    "#;
+
+    pub fn new(display: &Display) -> Self {
+        let corners = [[-1., -1.], [1., -1.], [-1., 1.], [1., 1.]].map(|pos| QuadVertex { pos });
+
+        Self {
+            vertex_buffer: VertexBuffer::new(display, &corners).unwrap(),
+        }
+    }
+
+    pub fn draw<S: Surface, U: glium::uniforms::Uniforms>(&self, target: &mut S, program: &Program, uniforms: &U, params: &glium::DrawParameters) {
+        target.draw(
+            &self.vertex_buffer,
+            glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+            program,
+            uniforms,
+            params,
         ).unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "#version 140\n#include \"gradient.glsl\"\nvoid main() {}\n";
+
+    /// String number of a library chunk, as `expand_includes` numbers them.
+    fn string_of(name: &str) -> usize {
+        LIBRARY.iter().position(|file| file.name == name).unwrap() + 1
+    }
+
+    #[test]
+    fn reads_mesa_locations() {
+        assert_eq!(log_location("0:12(5): error: `foo' undeclared"), Some((0, 12)));
+        assert_eq!(log_location("ERROR: 2:7: 'bar' : undeclared identifier"), Some((2, 7)));
+    }
+
+    #[test]
+    fn reads_nvidia_locations() {
+        assert_eq!(log_location("0(12) : error C0000: syntax error, unexpected '}'"), Some((0, 12)));
+        assert_eq!(log_location("3(40) : warning C7050: \"x\" might be used before being initialized"), Some((3, 40)));
+    }
+
+    #[test]
+    fn ignores_entries_without_a_location() {
+        assert_eq!(log_location("error: linking failed"), None);
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let expanded = expand_includes(SOURCE);
+
+        assert!(expanded.contains(&format!("#line 1 {}\n", string_of("gradient.glsl"))));
+        assert!(expanded.contains(&format!("#line 1 {}\n", string_of("hsluv.glsl"))));
+        assert!(!expanded.contains("#include"));
+    }
+
+    #[test]
+    fn includes_each_chunk_once() {
+        let expanded = expand_includes("#include \"hsluv.glsl\"\n#include \"gradient.glsl\"\n#include \"hsluv.glsl\"\n");
+
+        let header = format!("#line 1 {}\n", string_of("hsluv.glsl"));
+        assert_eq!(expanded.matches(&header).count(), 1);
+    }
+
+    #[test]
+    fn unknown_includes_become_errors() {
+        let expanded = expand_includes("#include \"missing.glsl\"\n");
+        assert_eq!(expanded, "#error unknown include missing.glsl\n");
+    }
+
+    #[test]
+    fn maps_lines_back_to_the_library() {
+        let expanded = expand_includes(SOURCE);
+
+        let gradient = LIBRARY[string_of("gradient.glsl") - 1].read();
+        let hsluv = LIBRARY[string_of("hsluv.glsl") - 1].read();
+        assert_eq!(source_line(&expanded, string_of("gradient.glsl"), 5), gradient.lines().nth(4));
+        assert_eq!(source_line(&expanded, string_of("hsluv.glsl"), 2), hsluv.lines().nth(1));
+    }
+
+    #[test]
+    fn keeps_numbering_after_an_include() {
+        let expanded = expand_includes(SOURCE);
+        assert_eq!(source_line(&expanded, 0, 3), Some("void main() {}"));
+    }
+
+    #[test]
+    fn keeps_numbering_after_a_define() {
+        let defined = insert_after_version(SOURCE, "#define SIZE 4");
+        let expanded = expand_includes(&defined);
+
+        let (string, number) = log_location("0:3(1): error: syntax error").unwrap();
+        assert_eq!(source_line(&expanded, string, number), Some("void main() {}"));
+    }
+}
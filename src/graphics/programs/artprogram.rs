@@ -0,0 +1,121 @@
+use glium::{
+    texture::RawImage2d,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Frame,
+    Program,
+    Rect,
+    Texture2d,
+};
+
+use crate::{
+    graphics::{
+        programs::{
+            ScreenQuad,
+            ShaderSrc,
+        },
+        Display,
+    },
+    metadata::Art,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct ArtSettings {
+    /// Side of the art as a fraction of the panel height.
+    pub size: f32,
+    pub opacity: f32,
+    /// Gap to the bottom left corner of the panel, in pixels.
+    pub margin: u32,
+}
+
+/// Cover art of the current track in the bottom left corner of its panel,
+/// meant to be stacked over another visualisation.
+pub struct ArtProgram {
+    program: Program,
+    quad: ScreenQuad,
+    display: Display,
+    art: Option<Texture2d>,
+    /// Width over height of the art.
+    aspect: f32,
+    settings: ArtSettings,
+}
+
+impl ArtProgram {
+    pub fn new(display: &Display, settings: ArtSettings) -> Self {
+        let program = ShaderSrc {
+            vertex_shader: ScreenQuad::VERTEX_SHADER.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec2 uv;
+                    out vec4 color;
+
+                    uniform sampler2D art;
+                    uniform float opacity;
+
+                    void main() {
+                        color = vec4(texture(art, uv).rgb, opacity);
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        }.get_program(display);
+
+        Self {
+            program,
+            quad: ScreenQuad::new(display),
+            display: display.clone(),
+            art: None,
+            aspect: 1.,
+            settings,
+        }
+    }
+
+    /// Shows `art`, or nothing for tracks without any.
+    pub fn set_art(&mut self, art: Option<&Art>) {
+        self.art = art.map(|art| {
+            // Texture rows start at the bottom
+            let image = RawImage2d::from_raw_rgba_reversed(&art.pixels, (art.width, art.height));
+            Texture2d::new(&self.display, image).unwrap()
+        });
+        self.aspect = art.map_or(1., |art| art.width as f32 / art.height.max(1) as f32);
+    }
+
+    /// Where the art goes in `viewport`.
+    pub fn area(&self, viewport: Rect) -> Rect {
+        let margin = self.settings.margin;
+        let height = (viewport.height as f32 * self.settings.size) as u32;
+        let width = ((height as f32 * self.aspect) as u32).min(viewport.width.saturating_sub(2 * margin));
+
+        Rect {
+            left: viewport.left + margin,
+            bottom: viewport.bottom + margin,
+            width,
+            height: height.min(viewport.height.saturating_sub(2 * margin)),
+        }
+    }
+
+    pub fn render(&self, target: &mut Frame, viewport: Rect) {
+        let Some(art) = &self.art else {
+            return;
+        };
+        let area = self.area(viewport);
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        self.quad.draw(
+            target,
+            &self.program,
+            &glium::uniform! {
+                art: Sampler::new(art)
+                    .wrap_function(SamplerWrapFunction::Clamp)
+                    .minify_filter(MinifySamplerFilter::Linear)
+                    .magnify_filter(MagnifySamplerFilter::Linear),
+                opacity: self.settings.opacity,
+            },
+            &glium::DrawParameters {
+                viewport: Some(area),
+                blend: glium::Blend::alpha_blending(),
+                ..Default::default()
+            },
+        );
+    }
+}
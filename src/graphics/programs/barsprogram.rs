@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+
+use glium::{implement_vertex, Frame, Program, Rect, Surface, VertexBuffer};
+
+use crate::graphics::{
+    programs::{
+        spectrum_level,
+        spectrum_reference,
+        ShaderSrc,
+    },
+    theme::Gradient,
+    Display,
+};
+
+#[derive(Copy, Clone)]
+struct CornerVertex {
+    corner: [f32; 2],
+}
+implement_vertex!(CornerVertex, corner);
+
+#[derive(Default, Copy, Clone)]
+struct BarInstance {
+    height: f32,
+    peak: f32,
+}
+implement_vertex!(BarInstance, height, peak);
+
+#[derive(Clone, Copy, Debug)]
+pub struct BarSettings {
+    pub count: usize,
+    /// Fraction of each bar's slot left empty between bars.
+    pub gap: f32,
+    pub rounded: bool,
+    /// Grow up and down from the centre line instead of up from the bottom.
+    pub mirror: bool,
+    pub peak_hold: Duration,
+    /// Acceleration of falling peak caps, in screen heights per second squared.
+    pub peak_gravity: f32,
+}
+
+#[derive(Default, Clone, Copy)]
+struct PeakCap {
+    height: f32,
+    velocity: f32,
+    hold_left: f32,
+}
+
+impl PeakCap {
+    fn update(&mut self, height: f32, dt: f32, settings: &BarSettings) {
+        if height >= self.height {
+            self.height = height;
+            self.velocity = 0.;
+            self.hold_left = settings.peak_hold.as_secs_f32();
+        } else if self.hold_left > 0. {
+            self.hold_left -= dt;
+        } else {
+            self.velocity += settings.peak_gravity * dt;
+            self.height = (self.height - self.velocity * dt).max(height);
+        }
+    }
+}
+
+/// Classic bar-graph spectrum. A single unit quad is instanced once per bar,
+/// with the bar and cap heights as per-instance attributes.
+pub struct BarsProgram {
+    program: Program,
+    quad: VertexBuffer<CornerVertex>,
+    instances: VertexBuffer<BarInstance>,
+    instance_pre_buffer: Vec<BarInstance>,
+    caps: Vec<PeakCap>,
+    settings: BarSettings,
+    gradient: Gradient,
+    reference: f32,
+    last_render: Instant,
+}
+
+impl BarsProgram {
+    pub fn new(display: &Display, settings: BarSettings, fft_window: usize) -> Self {
+        let program = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in vec2 corner;
+                    in float height;
+                    in float peak;
+
+                    out vec2 local;
+                    out float screenY;
+                    flat out float barHeight;
+                    flat out float barFrequency;
+
+                    uniform int count;
+                    uniform float gap;
+                    uniform bool mirror;
+                    // 0 draws the bars, 1 the caps, 2 the lower caps in mirror mode
+                    uniform int pass;
+                    uniform float cap_height;
+
+                    void main() {
+                        float slot = 2.0 / count;
+                        barFrequency = (gl_InstanceID + 0.5) / count;
+                        float x = -1.0 + slot * (gl_InstanceID + gap / 2.0 + corner.x * (1.0 - gap));
+
+                        float y;
+                        if (pass == 0) {
+                            y = mirror ? mix(-height, height, corner.y) : corner.y * height * 2.0 - 1.0;
+                            barHeight = height * 2.0;
+                        } else {
+                            y = (mirror ? peak : peak * 2.0 - 1.0) + corner.y * cap_height;
+                            y = pass == 2 ? -y : y;
+                            barHeight = cap_height;
+                        }
+
+                        local = corner;
+                        screenY = mirror ? abs(y) : (y + 1.0) / 2.0;
+                        gl_Position = vec4(x, y, 0.0, 1.0);
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec2 local;
+                    in float screenY;
+                    flat in float barHeight;
+                    flat in float barFrequency;
+
+                    out vec4 color;
+
+                    #include "gradient.glsl"
+
+                    uniform bool rounded;
+                    uniform bool mirror;
+                    uniform int pass;
+                    uniform vec2 resolution;
+                    uniform float bar_width_px;
+
+                    void main() {
+                        if (rounded && pass == 0) {
+                            // Round the ends with a radius of half the bar width
+                            float radius = bar_width_px / 2.0;
+                            float height_px = barHeight * resolution.y / 2.0;
+                            vec2 px = vec2(local.x * bar_width_px, local.y * height_px);
+
+                            float top = height_px - radius;
+                            bool outside_top = px.y > top && distance(px, vec2(radius, top)) > radius;
+                            bool outside_bottom = mirror && px.y < radius && distance(px, vec2(radius, radius)) > radius;
+                            if (outside_top || outside_bottom) {
+                                discard;
+                            }
+                        }
+
+                        color = vec4(gradient(barFrequency, screenY), 1.0);
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        }.get_program(display);
+
+        let corners = [[0., 0.], [1., 0.], [0., 1.], [1., 1.]].map(|corner| CornerVertex { corner });
+
+        Self {
+            program,
+            quad: VertexBuffer::new(display, &corners).unwrap(),
+            instances: VertexBuffer::empty_dynamic(display, settings.count).unwrap(),
+            instance_pre_buffer: vec![BarInstance::default(); settings.count],
+            caps: vec![PeakCap::default(); settings.count],
+            settings,
+            gradient: Gradient::default(),
+            reference: spectrum_reference(fft_window),
+            last_render: Instant::now(),
+        }
+    }
+
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        self.gradient = gradient;
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, values: &[f32]) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_render).as_secs_f32();
+        self.last_render = now;
+
+        // Each bar shows the loudest of the bins it covers
+        let per_bar = values.len().div_ceil(self.settings.count).max(1);
+        for ((bar, cap), bins) in self.instance_pre_buffer.iter_mut()
+            .zip(self.caps.iter_mut())
+            .zip(values.chunks(per_bar))
+        {
+            let magnitude = bins.iter().copied().fold(0., f32::max);
+            let height = spectrum_level(magnitude, self.reference);
+
+            cap.update(height, dt, &self.settings);
+            *bar = BarInstance { height, peak: cap.height };
+        }
+        self.instances.write(&self.instance_pre_buffer);
+
+        let (width, height) = (viewport.width, viewport.height);
+        let bar_width_px = width as f32 / self.settings.count as f32 * (1. - self.settings.gap);
+        let cap_height = 8. / height as f32;
+
+        let passes: &[i32] = if self.settings.mirror { &[0, 1, 2] } else { &[0, 1] };
+        for &pass in passes {
+            target.draw(
+                (&self.quad, self.instances.per_instance().unwrap()),
+                glium::index::NoIndices(glium::index::PrimitiveType::TriangleStrip),
+                &self.program,
+                &glium::uniform! {
+                    count: self.settings.count as i32,
+                    gap: self.settings.gap,
+                    mirror: self.settings.mirror,
+                    rounded: self.settings.rounded,
+                    pass: pass,
+                    cap_height: cap_height,
+                    gradient_low: self.gradient.low,
+                    gradient_high: self.gradient.high,
+                    gradient_axis: self.gradient.axis as i32,
+                    resolution: [width as f32, height as f32],
+                    bar_width_px: bar_width_px,
+                },
+                &glium::DrawParameters {
+                    viewport: Some(viewport),
+                    ..Default::default()
+                },
+            ).unwrap();
+        }
+    }
+}
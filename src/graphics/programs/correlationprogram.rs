@@ -0,0 +1,111 @@
+
+use glium::{implement_vertex, uniforms::EmptyUniforms, Frame, Rect};
+
+use crate::graphics::{
+    programs::{
+        ProgramRunner,
+        ShaderSrc,
+        Decay,
+    },
+    Display,
+};
+
+#[derive(Default, Copy, Clone, Debug)]
+struct BarVertex {
+    pos: [f32; 2],
+    colour: [f32; 3],
+}
+implement_vertex!(BarVertex, pos, colour);
+
+impl Decay<BarVertex> for BarVertex {
+    fn assign(&mut self, rhs: BarVertex) {
+        *self = rhs;
+    }
+}
+
+fn quad(x: (f32, f32), y: (f32, f32), colour: [f32; 3]) -> [BarVertex; 6] {
+    let v = |x, y| BarVertex { pos: [x, y], colour };
+    [
+        v(x.0, y.0), v(x.1, y.0), v(x.1, y.1),
+        v(x.0, y.0), v(x.1, y.1), v(x.0, y.1),
+    ]
+}
+
+const HALF_WIDTH: f32 = 0.95;
+const TRACK: [f32; 3] = [0.15, 0.15, 0.15];
+const CENTRE: [f32; 3] = [0.5, 0.5, 0.5];
+
+// Track, centre line and indicator for each of the two bars
+const QUADS: usize = 2 * 3;
+
+/// Horizontal correlation (-1..+1) and balance (L..R) bars, one above the
+/// other.
+pub struct CorrelationProgram {
+    prog: ProgramRunner<BarVertex, BarVertex>,
+    vertices: Vec<BarVertex>,
+}
+
+impl CorrelationProgram {
+    pub fn new(display: &Display) -> Self {
+        let shaders = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in vec2 pos;
+                    in vec3 colour;
+
+                    out vec3 colourFrag;
+
+                    void main() {
+                        gl_Position = vec4(pos, 0.0, 1.0);
+                        colourFrag = colour;
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec3 colourFrag;
+
+                    out vec4 color;
+
+                    void main() {
+                        color = vec4(colourFrag, 1.0);
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        };
+
+        Self {
+            prog: ProgramRunner::new(QUADS * 6, display, shaders),
+            vertices: Vec::with_capacity(QUADS * 6),
+        }
+    }
+
+    fn bar(&mut self, y: (f32, f32), value: f32, colour: [f32; 3]) {
+        let x = value.clamp(-1., 1.) * HALF_WIDTH;
+
+        self.vertices.extend(quad((-HALF_WIDTH, HALF_WIDTH), y, TRACK));
+        self.vertices.extend(quad((x.min(0.), x.max(0.)), y, colour));
+        self.vertices.extend(quad((-0.005, 0.005), (y.0 - 0.1, y.1 + 0.1), CENTRE));
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, correlation: f32, balance: f32) {
+        self.vertices.clear();
+
+        // Negative correlation means trouble when summed to mono
+        let correlation_colour = if correlation < 0. {
+            [1.0, 0.1, 0.1]
+        } else {
+            [0.1, 0.8, 0.2]
+        };
+
+        self.bar((0.15, 0.75), correlation, correlation_colour);
+        self.bar((-0.75, -0.15), balance, [0.2, 0.5, 1.0]);
+
+        self.prog.render(
+            target,
+            viewport,
+            &self.vertices,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &EmptyUniforms,
+        );
+    }
+}
@@ -1,9 +1,15 @@
 
-use glium::{implement_vertex, uniforms::Uniforms, Frame};
+use std::path::PathBuf;
+
+use glium::{implement_vertex, uniforms::Uniforms, Frame, Rect};
 
 use crate::graphics::{
     programs::{
+        line_geometry_shader,
+        line_strip_indices,
+        spectrum_reference,
         ProgramRunner,
+        ShaderError,
         ShaderSrc,
         Decay,
     }, 
@@ -11,18 +17,20 @@ use crate::graphics::{
 };
 
 #[derive(Default, Copy, Clone)]
-struct FFTVertex {
+pub(super) struct FFTVertex {
     ampl: f32,
 }
 implement_vertex!(FFTVertex, ampl);
 
 struct FFTUniform {
     colour: [f32; 3],
+    width: f32,
 }
 
 impl Uniforms for FFTUniform {
     fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
         f("colour", glium::uniforms::UniformValue::Vec3(self.colour));
+        f("width", glium::uniforms::UniformValue::Float(self.width));
     }
 }
 
@@ -34,45 +42,183 @@ impl Decay<f32> for FFTVertex {
 }
 pub struct FFTProgram { 
     prog: ProgramRunner<f32, FFTVertex>, 
-    uniforms: FFTUniform
+    indices: glium::IndexBuffer<u32>,
+    uniforms: FFTUniform,
+    display: Display,
 }
 
 impl FFTProgram {
-    pub fn new(size: usize, display: &Display, colour: [f32; 3]) -> Self {
-        let uniforms= FFTUniform { colour };
-        let shaders = ShaderSrc {
-            vertex_shader: format!(r#"
-                    #version 140
-                    in float ampl;
-            
-                    void main() {{
-                        gl_Position = vec4((gl_VertexID / {size}.0 - 0.5) * 1.8, log2(ampl * inversesqrt({size}.0)) / log2(20.0) / 10, 0.0, 1.0);
-                    }}
-                "#),
-            fragment_shader: r#"
-                    #version 140
-                    out vec4 color;
-                    uniform vec3 colour;
-
-                    void main() {
-                        color = vec4(colour, 1.0);
-                    }
-                "#.to_string(),
-            geometry_shader: None,
-        };
+    /// `width` is the line thickness in pixels.
+    pub fn new(size: usize, display: &Display, width: f32) -> Self {
+        let uniforms= FFTUniform { colour: [1., 1., 1.], width };
+        let vertex = shader_file!("fft.vert");
+        let fragment = shader_file!("fft.frag");
+        let shaders = move || ShaderSrc {
+            vertex_shader: vertex.read(),
+            fragment_shader: fragment.read(),
+            geometry_shader: Some(line_geometry_shader(None)),
+        }.define("SIZE", size);
+
+        let mut prog = ProgramRunner::reloadable(size, display, &[vertex, fragment], shaders);
+        prog.set_draw_parameters(glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        });
 
         Self {
-            prog: ProgramRunner::new(size, display, shaders),
-            uniforms
+            prog,
+            indices: line_strip_indices(display, size),
+            uniforms,
+            display: display.clone(),
         }
     }
 
-    pub fn render(&mut self, target: &mut Frame, values: &[f32]) {
+    pub fn set_colour(&mut self, colour: [f32; 3]) {
+        self.uniforms.colour = colour;
+    }
+
+    pub fn reload(&mut self, changed: &[PathBuf]) -> Result<(), ShaderError> {
+        self.prog.reload(&self.display, changed)
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, values: &[f32]) {
         self.prog.render(
             target,
+            viewport,
             values, 
-            glium::index::NoIndices(glium::index::PrimitiveType::LineStrip), 
+            &self.indices, 
             &self.uniforms
         );
     }
 }
+
+/// Frequencies marked by spectrum gridlines.
+#[derive(Clone, Copy, Debug)]
+pub enum FrequencyGrid {
+    /// Standard octave band centres, 16 Hz to 16 kHz.
+    Octave,
+    /// Powers of ten, with fainter lines at each multiple in between.
+    Decade,
+}
+
+/// Nominal octave band centres.
+const OCTAVES: [f32; 11] = [16., 31.5, 63., 125., 250., 500., 1000., 2000., 4000., 8000., 16000.];
+
+impl FrequencyGrid {
+    /// Grid after `grid` when cycling through none, octaves and decades.
+    pub fn cycle(grid: Option<Self>) -> Option<Self> {
+        match grid {
+            None => Some(FrequencyGrid::Octave),
+            Some(FrequencyGrid::Octave) => Some(FrequencyGrid::Decade),
+            Some(FrequencyGrid::Decade) => None,
+        }
+    }
+
+    /// Gridline frequencies below `max`, each with whether it is a major line.
+    pub fn lines(&self, max: f32) -> Vec<(f32, bool)> {
+        let lines: Vec<(f32, bool)> = match self {
+            FrequencyGrid::Octave => OCTAVES.iter().map(|&frequency| (frequency, true)).collect(),
+            FrequencyGrid::Decade => [10., 100., 1000., 10000.].into_iter()
+                .flat_map(|decade| (1..10).map(move |multiple| (decade * multiple as f32, multiple == 1)))
+                .collect(),
+        };
+        lines.into_iter().filter(|&(frequency, _)| frequency < max).collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GridSettings {
+    /// Vertical lines, or none.
+    pub frequencies: Option<FrequencyGrid>,
+    /// Gap between horizontal lines in dB, or none.
+    pub db_step: Option<f32>,
+    pub opacity: f32,
+}
+
+/// Where frequencies and levels land in the spectrum drawn by `FFTProgram`,
+/// in normalised device coordinates of its viewport. Mirrors `fft.vert`.
+#[derive(Clone, Copy, Debug)]
+pub struct SpectrumAxes {
+    size: usize,
+    bin_width: f32,
+    /// Level in dB of a full scale sine, as the shader sees it.
+    full_scale: f32,
+}
+
+impl SpectrumAxes {
+    pub fn new(size: usize, bin_width: f32, fft_window: usize) -> Self {
+        Self {
+            size,
+            bin_width,
+            full_scale: 20. * (spectrum_reference(fft_window) / (size as f32).sqrt()).log10(),
+        }
+    }
+
+    /// Follows the processor to a new sample rate.
+    pub fn set_bin_width(&mut self, bin_width: f32) {
+        self.bin_width = bin_width;
+    }
+
+    pub fn x(&self, frequency: f32) -> f32 {
+        (frequency / self.bin_width / self.size as f32 - 0.5) * 1.8
+    }
+
+    /// Frequency at `x`, the inverse of `x`.
+    pub fn frequency(&self, x: f32) -> f32 {
+        (x / 1.8 + 0.5) * self.size as f32 * self.bin_width
+    }
+
+    /// Index of the bin holding `frequency`.
+    pub fn bin(&self, frequency: f32) -> usize {
+        ((frequency / self.bin_width).max(0.) as usize).min(self.size - 1)
+    }
+
+    /// Height of a level in dB relative to full scale.
+    pub fn y(&self, db: f32) -> f32 {
+        (db + self.full_scale) / (200. * 20f32.log10())
+    }
+
+    /// Level in dB relative to full scale of a bin `magnitude`.
+    pub fn level(&self, magnitude: f32) -> f32 {
+        20. * (magnitude / (self.size as f32).sqrt()).max(f32::MIN_POSITIVE).log10() - self.full_scale
+    }
+
+    /// Highest frequency shown.
+    pub fn max_frequency(&self) -> f32 {
+        self.bin_width * self.size as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axes() -> SpectrumAxes {
+        SpectrumAxes::new(1024, 48000. / 2048., 2048)
+    }
+
+    #[test]
+    fn frequency_inverts_x() {
+        let axes = axes();
+        for x in [-0.9, -0.25, 0., 0.4, 0.9] {
+            assert!((axes.x(axes.frequency(x)) - x).abs() < 1e-5, "{x}");
+        }
+        assert_eq!(axes.frequency(0.9), axes.max_frequency());
+    }
+
+    #[test]
+    fn bin_is_clamped_to_the_spectrum() {
+        let axes = axes();
+        assert_eq!(axes.bin(-100.), 0);
+        assert_eq!(axes.bin(1000.), 42);
+        assert_eq!(axes.bin(axes.max_frequency()), 1023);
+        assert_eq!(axes.bin(96000.), 1023);
+    }
+
+    #[test]
+    fn full_scale_sine_reads_zero_db() {
+        let axes = axes();
+        assert!(axes.level(spectrum_reference(2048)).abs() < 1e-3);
+        assert!((axes.level(spectrum_reference(2048) / 10.) + 20.).abs() < 1e-3);
+    }
+}
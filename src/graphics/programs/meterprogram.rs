@@ -0,0 +1,187 @@
+
+use glium::{implement_vertex, uniforms::Uniforms, Frame, Rect};
+
+use crate::graphics::{
+    programs::{
+        ProgramRunner,
+        ShaderSrc,
+        Decay,
+    },
+    Display,
+};
+
+#[derive(Default, Copy, Clone, Debug)]
+pub(super) struct MeterVertex {
+    pos: [f32; 2],
+    db: f32,
+}
+implement_vertex!(MeterVertex, pos, db);
+
+impl Decay<MeterVertex> for MeterVertex {
+    fn assign(&mut self, rhs: MeterVertex) {
+        *self = rhs;
+    }
+}
+
+/// Maps a level onto a vertical meter scale spanning the whole viewport.
+pub(super) struct MeterScale {
+    pub min_db: f32,
+    pub max_db: f32,
+}
+
+impl MeterScale {
+    fn fraction(&self, db: f32) -> f32 {
+        ((db - self.min_db) / (self.max_db - self.min_db)).clamp(0., 1.)
+    }
+
+    fn y(&self, db: f32) -> f32 {
+        self.fraction(db) * 2. - 1.
+    }
+
+    /// Quad spanning the given dB range, so the colour follows the meter scale.
+    pub fn quad(&self, x: (f32, f32), db: (f32, f32)) -> [MeterVertex; 6] {
+        let v = |x, db| MeterVertex { pos: [x, self.y(db)], db };
+        [
+            v(x.0, db.0), v(x.1, db.0), v(x.1, db.1),
+            v(x.0, db.0), v(x.1, db.1), v(x.0, db.1),
+        ]
+    }
+}
+
+/// Quad of the given width along the segment from `a` to `b`.
+fn segment(a: [f32; 2], b: [f32; 2], width: f32, db: f32) -> [MeterVertex; 6] {
+    let dir = [b[0] - a[0], b[1] - a[1]];
+    let len = (dir[0] * dir[0] + dir[1] * dir[1]).sqrt().max(f32::EPSILON);
+    let n = [-dir[1] / len * width / 2., dir[0] / len * width / 2.];
+
+    let v = |p: [f32; 2], s: f32| MeterVertex { pos: [p[0] + n[0] * s, p[1] + n[1] * s], db };
+    [
+        v(a, 1.), v(a, -1.), v(b, -1.),
+        v(a, 1.), v(b, -1.), v(b, 1.),
+    ]
+}
+
+pub(super) struct MeterUniform {
+    /// Levels above these are drawn yellow and red respectively.
+    pub warn_db: f32,
+    pub danger_db: f32,
+}
+
+impl Uniforms for MeterUniform {
+    fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
+        f("warn_db", glium::uniforms::UniformValue::Float(self.warn_db));
+        f("danger_db", glium::uniforms::UniformValue::Float(self.danger_db));
+    }
+}
+
+pub(super) fn meter_shaders() -> ShaderSrc {
+    ShaderSrc {
+        vertex_shader: r#"
+                #version 140
+                in vec2 pos;
+                in float db;
+
+                out float dbFrag;
+
+                void main() {
+                    gl_Position = vec4(pos, 0.0, 1.0);
+                    dbFrag = db;
+                }
+            "#.to_string(),
+        fragment_shader: r#"
+                #version 140
+                in float dbFrag;
+
+                out vec4 color;
+
+                uniform float warn_db;
+                uniform float danger_db;
+
+                void main() {
+                    vec3 green = vec3(0.1, 0.8, 0.2);
+                    vec3 yellow = vec3(0.9, 0.8, 0.1);
+                    vec3 red = vec3(1.0, 0.1, 0.1);
+
+                    vec3 c = dbFrag < warn_db ? green : (dbFrag < danger_db ? yellow : red);
+                    color = vec4(c, 1.0);
+                }
+            "#.to_string(),
+        geometry_shader: None,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MeterStyle {
+    Bar,
+    Needle,
+}
+
+const TICKS: [f32; 7] = [-20., -10., -7., -5., -3., 0., 3.];
+const NEEDLE_SWEEP: f32 = std::f32::consts::FRAC_PI_2;
+
+/// Level meter for the `processing::meter` ballistics, filling its viewport
+/// with one bar or gauge per channel. Readings are shown relative to
+/// `reference_db`, so with the usual -18 dBFS alignment 0 on the scale is
+/// 0 VU.
+pub struct MeterProgram {
+    prog: ProgramRunner<MeterVertex, MeterVertex>,
+    uniforms: MeterUniform,
+    vertices: Vec<MeterVertex>,
+    style: MeterStyle,
+    reference_db: f32,
+}
+
+impl MeterProgram {
+    pub fn new(display: &Display, style: MeterStyle, reference_db: f32) -> Self {
+        let size = match style {
+            MeterStyle::Bar => 2 * 6,
+            MeterStyle::Needle => 2 * (TICKS.len() + 1) * 6,
+        };
+
+        Self {
+            prog: ProgramRunner::new(size, display, meter_shaders()),
+            uniforms: MeterUniform { warn_db: -3., danger_db: 0. },
+            vertices: Vec::with_capacity(size),
+            style,
+            reference_db,
+        }
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, left: f32, right: f32) {
+        self.vertices.clear();
+
+        let levels = [left - self.reference_db, right - self.reference_db];
+        let scale = MeterScale { min_db: TICKS[0], max_db: TICKS[TICKS.len() - 1] };
+
+        match self.style {
+            MeterStyle::Bar => {
+                for (i, level) in levels.into_iter().enumerate() {
+                    let x0 = -0.9 + i as f32;
+                    self.vertices.extend(scale.quad((x0, x0 + 0.8), (scale.min_db, level.max(scale.min_db))));
+                }
+            },
+            MeterStyle::Needle => {
+                for (i, level) in levels.into_iter().enumerate() {
+                    let pivot = [-0.5 + i as f32, -0.8];
+                    let point = |db: f32, r: f32| {
+                        let angle = std::f32::consts::FRAC_PI_2 + NEEDLE_SWEEP * (0.5 - scale.fraction(db));
+                        [pivot[0] + angle.cos() * r, pivot[1] + angle.sin() * r]
+                    };
+
+                    for tick in TICKS {
+                        self.vertices.extend(segment(point(tick, 1.4), point(tick, 1.6), 0.02, tick));
+                    }
+                    self.vertices.extend(segment(pivot, point(level, 1.5), 0.015, level));
+                }
+            },
+        }
+
+        self.prog.render(
+            target,
+            viewport,
+            &self.vertices,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.uniforms,
+        );
+    }
+}
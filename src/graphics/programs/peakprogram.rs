@@ -0,0 +1,65 @@
+
+use glium::{Frame, Rect};
+
+use crate::graphics::{
+    programs::{
+        meterprogram::{meter_shaders, MeterScale, MeterUniform, MeterVertex},
+        ProgramRunner,
+    },
+    Display,
+};
+
+use crate::processing::peak::PeakLevels;
+
+const SCALE: MeterScale = MeterScale { min_db: -60., max_db: 3. };
+
+// Two bars (sample and true peak) per channel, each a bar and a hold marker
+const QUADS: usize = 2 * 2 * 2;
+
+/// Sample-peak and true-peak bars with peak-hold markers, left channel first,
+/// filling the viewport.
+pub struct PeakProgram {
+    prog: ProgramRunner<MeterVertex, MeterVertex>,
+    uniforms: MeterUniform,
+    vertices: Vec<MeterVertex>,
+}
+
+impl PeakProgram {
+    pub fn new(display: &Display) -> Self {
+        Self {
+            prog: ProgramRunner::new(QUADS * 6, display, meter_shaders()),
+            uniforms: MeterUniform { warn_db: -18., danger_db: -1. },
+            vertices: Vec::with_capacity(QUADS * 6),
+        }
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, left: &PeakLevels, right: &PeakLevels) {
+        self.vertices.clear();
+
+        let bar_width = 0.4;
+        let marker = 0.5;
+
+        let bars = [
+            (left.sample, left.sample_hold),
+            (left.true_peak, left.true_hold),
+            (right.sample, right.sample_hold),
+            (right.true_peak, right.true_hold),
+        ];
+
+        for (i, (level, hold)) in bars.into_iter().enumerate() {
+            let x0 = -0.95 + i as f32 * (bar_width + 0.05) + (i / 2) as f32 * 0.15;
+            let x = (x0, x0 + bar_width);
+
+            self.vertices.extend(SCALE.quad(x, (SCALE.min_db, level.max(SCALE.min_db))));
+            self.vertices.extend(SCALE.quad(x, (hold - marker, hold)));
+        }
+
+        self.prog.render(
+            target,
+            viewport,
+            &self.vertices,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.uniforms,
+        );
+    }
+}
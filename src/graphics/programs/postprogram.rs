@@ -0,0 +1,330 @@
+use std::time::Instant;
+
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    texture::{MipmapsOption, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    BlitTarget,
+    Frame,
+    Program,
+    Surface,
+    Texture2d,
+};
+
+use crate::graphics::{
+    programs::{
+        ScreenQuad,
+        ShaderSrc,
+    },
+    Display,
+};
+
+/// Effect parameter that can be pushed around by the beat.
+#[derive(Clone, Copy, Debug)]
+pub struct Param {
+    pub value: f32,
+    /// Added to `value` on each beat, decaying along with the beat.
+    pub beat: f32,
+}
+
+impl Param {
+    fn at(&self, beat: f32) -> f32 {
+        self.value + self.beat * beat
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Effect {
+    /// Blurred copy of everything brighter than `threshold` added back on top.
+    /// `radius` is in pixels.
+    Bloom { threshold: Param, radius: Param, intensity: Param },
+    /// Blurred copy of everything added back on top, giving lines a soft halo.
+    Glow { radius: Param, intensity: Param },
+    /// Red and blue pulled apart towards the edges, by `offset` of the frame
+    /// size at the corners.
+    ChromaticAberration { offset: Param },
+    /// Darkens the frame from `radius` (0 at the centre, 1 at the corners)
+    /// outwards.
+    Vignette { radius: Param, strength: Param },
+    /// The previous output drawn behind the frame, zoomed by `zoom` and
+    /// rotated by `rotation` radians per second, keeping `decay` of its
+    /// brightness after a second. Trails spiral out as in MilkDrop.
+    Feedback { zoom: Param, rotation: Param, decay: Param },
+}
+
+const COPY_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+
+    void main() {
+        color = texture(source, uv);
+    }
+"#;
+
+const BRIGHT_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    uniform float threshold;
+
+    void main() {
+        vec4 c = texture(source, uv);
+        float l = max(c.r, max(c.g, c.b));
+        color = c * max(l - threshold, 0.0) / max(l, 0.0001);
+    }
+"#;
+
+const BLUR_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    // Distance between taps in uv units, along the blur direction
+    uniform vec2 direction;
+
+    const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    void main() {
+        vec4 sum = texture(source, uv) * weights[0];
+        for (int i = 1; i < 5; i++) {
+            sum += texture(source, uv + direction * float(i)) * weights[i];
+            sum += texture(source, uv - direction * float(i)) * weights[i];
+        }
+        color = sum;
+    }
+"#;
+
+const ADD_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    uniform sampler2D glow;
+    uniform float intensity;
+
+    void main() {
+        color = texture(source, uv) + texture(glow, uv) * intensity;
+    }
+"#;
+
+const CHROMATIC_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    uniform float offset;
+
+    void main() {
+        vec2 d = (uv - 0.5) * 2.0 * offset;
+        vec4 c = texture(source, uv);
+        color = vec4(texture(source, uv + d).r, c.g, texture(source, uv - d).b, c.a);
+    }
+"#;
+
+const VIGNETTE_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    uniform float radius;
+    uniform float strength;
+
+    void main() {
+        vec4 c = texture(source, uv);
+        float d = length(uv - 0.5) * sqrt(2.0);
+        color = vec4(c.rgb * (1.0 - strength * smoothstep(radius, 1.0, d)), c.a);
+    }
+"#;
+
+const FEEDBACK_SHADER: &str = r#"
+    #version 140
+    in vec2 uv;
+    out vec4 color;
+
+    uniform sampler2D source;
+    uniform sampler2D previous;
+    // Maps the frame onto the previous one, in pixel-square units
+    uniform mat2 warp;
+    uniform float aspect;
+    uniform float decay;
+
+    void main() {
+        vec2 scale = vec2(aspect, 1.0);
+        vec2 p = warp * ((uv - 0.5) * scale) / scale + 0.5;
+
+        vec4 behind = vec4(0.0);
+        if (all(greaterThanEqual(p, vec2(0.0))) && all(lessThanEqual(p, vec2(1.0)))) {
+            behind = texture(previous, p) * decay;
+        }
+        color = max(texture(source, uv), behind);
+    }
+"#;
+
+fn pass(display: &Display, fragment_shader: &str) -> Program {
+    ShaderSrc {
+        vertex_shader: ScreenQuad::VERTEX_SHADER.to_string(),
+        fragment_shader: fragment_shader.to_string(),
+        geometry_shader: None,
+    }.get_program(display)
+}
+
+fn sampled(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    Sampler::new(texture)
+        .wrap_function(SamplerWrapFunction::Clamp)
+        .minify_filter(MinifySamplerFilter::Linear)
+        .magnify_filter(MagnifySamplerFilter::Linear)
+}
+
+fn texture(display: &Display, width: u32, height: u32) -> Texture2d {
+    let texture = Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        width.max(1),
+        height.max(1),
+    ).unwrap();
+    texture.as_surface().clear_color(0., 0., 0., 0.);
+    texture
+}
+
+/// Offscreen targets, sized to the window.
+struct Targets {
+    /// Each effect reads one and writes the other.
+    swap: [Texture2d; 2],
+    /// Half size, for blurring.
+    blur: [Texture2d; 2],
+    /// Output of the feedback effect on the previous frame.
+    previous: Texture2d,
+}
+
+impl Targets {
+    fn new(display: &Display, (width, height): (u32, u32)) -> Self {
+        Self {
+            swap: [texture(display, width, height), texture(display, width, height)],
+            blur: [texture(display, width / 2, height / 2), texture(display, width / 2, height / 2)],
+            previous: texture(display, width, height),
+        }
+    }
+}
+
+/// Chain of full-frame effects applied after all panels are drawn. The frame
+/// is copied into a texture, run through the effects in order, and drawn back.
+pub struct PostProgram {
+    effects: Vec<Effect>,
+    display: Display,
+    quad: ScreenQuad,
+
+    copy: Program,
+    bright: Program,
+    blur: Program,
+    add: Program,
+    chromatic: Program,
+    vignette: Program,
+    feedback: Program,
+
+    targets: Option<Targets>,
+    last_render: Instant,
+}
+
+impl PostProgram {
+    pub fn new(display: &Display, effects: Vec<Effect>) -> Self {
+        Self {
+            effects,
+            display: display.clone(),
+            quad: ScreenQuad::new(display),
+
+            copy: pass(display, COPY_SHADER),
+            bright: pass(display, BRIGHT_SHADER),
+            blur: pass(display, BLUR_SHADER),
+            add: pass(display, ADD_SHADER),
+            chromatic: pass(display, CHROMATIC_SHADER),
+            vignette: pass(display, VIGNETTE_SHADER),
+            feedback: pass(display, FEEDBACK_SHADER),
+
+            targets: None,
+            last_render: Instant::now(),
+        }
+    }
+
+    /// Blurs the parts of `source` above `threshold` and adds them on top.
+    fn bloom(&self, targets: &Targets, source: &Texture2d, output: &mut SimpleFrameBuffer, threshold: f32, radius: f32, intensity: f32) {
+        let [a, b] = &targets.blur;
+        let (width, height) = source.dimensions();
+        let step = radius / 4.;
+        let params = Default::default();
+
+        self.quad.draw(&mut a.as_surface(), &self.bright, &glium::uniform! { source: sampled(source), threshold: threshold }, &params);
+        self.quad.draw(&mut b.as_surface(), &self.blur, &glium::uniform! { source: sampled(a), direction: [step / width as f32, 0.] }, &params);
+        self.quad.draw(&mut a.as_surface(), &self.blur, &glium::uniform! { source: sampled(b), direction: [0., step / height as f32] }, &params);
+        self.quad.draw(output, &self.add, &glium::uniform! { source: sampled(source), glow: sampled(a), intensity: intensity }, &params);
+    }
+
+    pub fn render(&mut self, target: &mut Frame, beat: f32) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_render).as_secs_f32();
+        self.last_render = now;
+
+        let dimensions = target.get_dimensions();
+        if self.targets.as_ref().map(|t| t.previous.dimensions()) != Some(dimensions) {
+            self.targets = Some(Targets::new(&self.display, dimensions));
+        }
+        let targets = self.targets.as_ref().unwrap();
+
+        let whole = BlitTarget { left: 0, bottom: 0, width: dimensions.0 as i32, height: dimensions.1 as i32 };
+        target.blit_whole_color_to(&targets.swap[0].as_surface(), &whole, MagnifySamplerFilter::Nearest);
+
+        let params = Default::default();
+        let mut current = 0;
+        for effect in &self.effects {
+            let source = &targets.swap[current];
+            let mut output = targets.swap[1 - current].as_surface();
+
+            match *effect {
+                Effect::Bloom { threshold, radius, intensity } => {
+                    self.bloom(targets, source, &mut output, threshold.at(beat), radius.at(beat), intensity.at(beat));
+                },
+                Effect::Glow { radius, intensity } => {
+                    self.bloom(targets, source, &mut output, 0., radius.at(beat), intensity.at(beat));
+                },
+                Effect::ChromaticAberration { offset } => {
+                    self.quad.draw(&mut output, &self.chromatic, &glium::uniform! {
+                        source: sampled(source),
+                        offset: offset.at(beat),
+                    }, &params);
+                },
+                Effect::Vignette { radius, strength } => {
+                    self.quad.draw(&mut output, &self.vignette, &glium::uniform! {
+                        source: sampled(source),
+                        radius: radius.at(beat),
+                        strength: strength.at(beat),
+                    }, &params);
+                },
+                Effect::Feedback { zoom, rotation, decay } => {
+                    // Scaled by elapsed time, so trails move at the same speed at any frame rate
+                    let zoom = zoom.at(beat).powf(dt);
+                    let (sin, cos) = (rotation.at(beat) * dt).sin_cos();
+                    self.quad.draw(&mut output, &self.feedback, &glium::uniform! {
+                        source: sampled(source),
+                        previous: sampled(&targets.previous),
+                        warp: [[cos / zoom, -sin / zoom], [sin / zoom, cos / zoom]],
+                        aspect: dimensions.0 as f32 / dimensions.1 as f32,
+                        decay: decay.at(beat).clamp(0., 1.).powf(dt),
+                    }, &params);
+                    output.blit_whole_color_to(&targets.previous.as_surface(), &whole, MagnifySamplerFilter::Nearest);
+                },
+            }
+            current = 1 - current;
+        }
+
+        self.quad.draw(target, &self.copy, &glium::uniform! { source: sampled(&targets.swap[current]) }, &params);
+    }
+}
@@ -0,0 +1,147 @@
+
+use glium::{uniforms::Uniforms, Frame, Rect};
+
+use crate::graphics::{
+    programs::{
+        fftprogram::FFTVertex,
+        spectrum_reference,
+        ProgramRunner,
+        ShaderSrc,
+        SPECTRUM_MIN_DB,
+    },
+    Display,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RadialSettings {
+    /// Radius of the silent circle, as a fraction of the screen height.
+    pub inner_radius: f32,
+    /// Length of a full scale spike, as a fraction of the screen height.
+    pub length: f32,
+    /// Angle of the lowest bin, in radians anticlockwise from the right.
+    pub rotation: f32,
+    /// Wrap the spectrum around each half of the circle, mirrored about the
+    /// line through `rotation`.
+    pub mirror: bool,
+    /// How much the inner radius grows on a beat, as a fraction of itself.
+    pub pulse: f32,
+}
+
+struct RadialUniform {
+    settings: RadialSettings,
+    colour: [f32; 3],
+    count: i32,
+    beat: f32,
+    aspect: f32,
+    reference: f32,
+}
+
+impl Uniforms for RadialUniform {
+    fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
+        use glium::uniforms::UniformValue;
+
+        f("count", UniformValue::SignedInt(self.count));
+        f("inner_radius", UniformValue::Float(self.settings.inner_radius));
+        f("spike_length", UniformValue::Float(self.settings.length));
+        f("rotation", UniformValue::Float(self.settings.rotation));
+        f("pulse", UniformValue::Float(self.settings.pulse));
+        f("colour", UniformValue::Vec3(self.colour));
+        f("beat", UniformValue::Float(self.beat));
+        f("aspect", UniformValue::Float(self.aspect));
+        f("reference", UniformValue::Float(self.reference));
+        f("min_db", UniformValue::Float(SPECTRUM_MIN_DB));
+    }
+}
+
+/// Spectrum wrapped around a circle, with a radius that pulses on beats.
+/// Uses the same decaying vertices as the line display.
+pub struct RadialProgram {
+    prog: ProgramRunner<f32, FFTVertex>,
+    uniforms: RadialUniform,
+    values: Vec<f32>,
+}
+
+impl RadialProgram {
+    pub fn new(size: usize, display: &Display, settings: RadialSettings, fft_window: usize) -> Self {
+        // One extra vertex closes the loop
+        let count = if settings.mirror { size * 2 + 1 } else { size + 1 };
+
+        let shaders = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in float ampl;
+
+                    uniform int count;
+                    uniform float inner_radius;
+                    uniform float spike_length;
+                    uniform float rotation;
+                    uniform float pulse;
+                    uniform float beat;
+                    uniform float aspect;
+                    uniform float reference;
+                    uniform float min_db;
+
+                    void main() {
+                        float angle = rotation + 6.2831853 * float(gl_VertexID) / float(count - 1);
+
+                        float db = 20.0 * log(max(ampl / reference, 1e-10)) / log(10.0);
+                        float level = clamp(1.0 - db / min_db, 0.0, 1.0);
+                        float radius = inner_radius * (1.0 + pulse * beat) + level * spike_length;
+
+                        gl_Position = vec4(vec2(cos(angle) * aspect, sin(angle)) * radius, 0.0, 1.0);
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    out vec4 color;
+                    uniform vec3 colour;
+
+                    void main() {
+                        color = vec4(colour, 1.0);
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        };
+
+        Self {
+            prog: ProgramRunner::new(count, display, shaders),
+            uniforms: RadialUniform {
+                settings,
+                colour: [1., 1., 1.],
+                count: count as i32,
+                beat: 0.,
+                aspect: 1.,
+                reference: spectrum_reference(fft_window),
+            },
+            values: Vec::with_capacity(count),
+        }
+    }
+
+    pub fn set_colour(&mut self, colour: [f32; 3]) {
+        self.uniforms.colour = colour;
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, values: &[f32], beat: f32) {
+        let Some(&first) = values.first() else {
+            return;
+        };
+
+        self.values.clear();
+        self.values.extend_from_slice(values);
+        if self.uniforms.settings.mirror {
+            self.values.extend(values.iter().rev());
+        }
+        self.values.push(first);
+
+        self.uniforms.aspect = viewport.height as f32 / viewport.width as f32;
+        self.uniforms.beat = beat;
+
+        self.prog.render(
+            target,
+            viewport,
+            &self.values,
+            glium::index::NoIndices(glium::index::PrimitiveType::LineStrip),
+            &self.uniforms,
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use glium::{implement_vertex, uniforms::Uniforms, Frame, Rect};
+
+use crate::graphics::{
+    programs::{
+        line_geometry_shader,
+        line_strip_indices,
+        Decay,
+        ProgramRunner,
+        ShaderSrc,
+    },
+    Display,
+};
+
+#[derive(Default, Copy, Clone)]
+struct ScopeVertex {
+    pos: [f32; 2],
+}
+implement_vertex!(ScopeVertex, pos);
+
+// The trigger already holds the trace still, so smoothing would only smear it
+impl Decay<[f32; 2]> for ScopeVertex {
+    fn assign(&mut self, rhs: [f32; 2]) {
+        self.pos = rhs;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ScopeMode {
+    /// Both channels against time, drawn over each other.
+    Channels,
+    /// Left on the x axis against right on the y axis.
+    XY,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScopeStyle {
+    pub mode: ScopeMode,
+    /// Trace thickness in pixels.
+    pub width: f32,
+}
+
+struct ScopeUniform {
+    colour: [f32; 3],
+    width: f32,
+}
+
+impl Uniforms for ScopeUniform {
+    fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
+        f("colour", glium::uniforms::UniformValue::Vec3(self.colour));
+        f("width", glium::uniforms::UniformValue::Float(self.width));
+    }
+}
+
+/// Time-domain view of the triggered scope window, drawn as thick lines with
+/// the same geometry shader as the phase display.
+pub struct ScopeProgram {
+    left: ProgramRunner<[f32; 2], ScopeVertex>,
+    right: ProgramRunner<[f32; 2], ScopeVertex>,
+    indices: glium::IndexBuffer<u32>,
+    style: ScopeStyle,
+    /// Left and right trace colours; XY mode only uses the left one.
+    colours: ([f32; 3], [f32; 3]),
+    points: Vec<[f32; 2]>,
+    /// Samples per window the buffers hold.
+    size: usize,
+}
+
+impl ScopeProgram {
+    pub fn new(size: usize, display: &Display, style: ScopeStyle) -> Self {
+        let shaders = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in vec2 pos;
+
+                    void main() {
+                        gl_Position = vec4(pos, 0.0, 1.0);
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    out vec4 color;
+
+                    uniform vec3 colour;
+
+                    #include "line.glsl"
+
+                    void main() {
+                        color = vec4(colour, line_alpha());
+                    }
+                "#.to_string(),
+            geometry_shader: Some(line_geometry_shader(None)),
+        };
+
+        let runner = || {
+            let mut prog = ProgramRunner::new(size, display, shaders.clone());
+            prog.set_draw_parameters(glium::DrawParameters {
+                blend: glium::Blend::alpha_blending(),
+                ..Default::default()
+            });
+            prog
+        };
+
+        Self {
+            left: runner(),
+            right: runner(),
+            indices: line_strip_indices(display, size),
+            style,
+            colours: ([1., 1., 1.], [1., 1., 1.]),
+            points: Vec::with_capacity(size),
+            size,
+        }
+    }
+
+    /// Rebuilds the buffers for windows of `size` samples, keeping the mode
+    /// and colours.
+    pub fn set_window(&mut self, display: &Display, size: usize) {
+        if size == self.size {
+            return;
+        }
+        *self = Self {
+            style: self.style,
+            colours: self.colours,
+            ..Self::new(size, display, self.style)
+        };
+    }
+
+    /// Switches between the time and XY views.
+    pub fn next_mode(&mut self) {
+        self.style.mode = match self.style.mode {
+            ScopeMode::Channels => ScopeMode::XY,
+            ScopeMode::XY => ScopeMode::Channels,
+        };
+    }
+
+    pub fn set_colours(&mut self, left: [f32; 3], right: [f32; 3]) {
+        self.colours = (left, right);
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, left: &[f32], right: &[f32]) {
+        // Nothing to draw before the first window arrives
+        if left.len() < 2 {
+            return;
+        }
+        let indices = &self.indices;
+        let width = self.style.width;
+
+        match self.style.mode {
+            ScopeMode::XY => {
+                self.points.clear();
+                self.points.extend(left.iter().zip(right).map(|(&l, &r)| [l, r]));
+                self.left.render(target, viewport, &self.points, indices, &ScopeUniform { colour: self.colours.0, width });
+            },
+            ScopeMode::Channels => {
+                let step = 2. / (left.len() - 1) as f32;
+                for (runner, channel, colour) in [
+                    (&mut self.left, left, self.colours.0),
+                    (&mut self.right, right, self.colours.1),
+                ] {
+                    self.points.clear();
+                    self.points.extend(channel.iter().enumerate().map(|(i, &y)| [-1. + i as f32 * step, y]));
+                    runner.render(target, viewport, &self.points, indices, &ScopeUniform { colour, width });
+                }
+            },
+        }
+    }
+}
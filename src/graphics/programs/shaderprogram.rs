@@ -0,0 +1,207 @@
+use std::{borrow::Cow, path::{Path, PathBuf}, time::Instant};
+
+use glium::{
+    texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Frame,
+    Program,
+    Rect,
+    Texture2d,
+};
+
+use crate::graphics::{
+    programs::{
+        resample_spectrum,
+        spectrum_reference,
+        ScreenQuad,
+        ShaderSrc,
+        LIBRARY,
+    },
+    Display,
+};
+
+use crate::processing::{FrequencyScale, ProcessorOutput};
+
+/// Width of the audio texture, matching Shadertoy's.
+const AUDIO_TEXTURE_WIDTH: usize = 512;
+
+#[derive(Clone, Debug)]
+pub struct ShaderSettings {
+    /// Name used for the shader in the layout.
+    pub name: String,
+    /// Fragment shader defining `mainImage`, as on Shadertoy.
+    pub path: PathBuf,
+    /// Frequency axis of the spectrum row in `iChannel0`.
+    pub scale: FrequencyScale,
+}
+
+/// Declarations placed in front of every user shader. `#line 1` after it
+/// keeps compiler errors pointing at lines in the user's file. User shaders
+/// can `#include` the same library chunks as the built-in ones.
+const PRELUDE: &str = r#"
+    #version 140
+    out vec4 color;
+
+    uniform vec3 iResolution;
+    uniform float iTime;
+    uniform int iFrame;
+    // 1 on each beat, decaying towards 0
+    uniform float iBeat;
+    // 0 on each beat, rising to 1 at the next expected one
+    uniform float iBeatPhase;
+    uniform float iBass;
+    uniform float iMid;
+    uniform float iTreble;
+    // Row 0 is the spectrum and row 1 the waveform, both in 0..1. Sample at
+    // y = 0.25 and 0.75 respectively.
+    uniform sampler2D iChannel0;
+
+    uniform vec2 viewport_offset;
+
+    void mainImage(out vec4 fragColor, in vec2 fragCoord);
+
+    void main() {
+        mainImage(color, gl_FragCoord.xy - viewport_offset);
+    }
+#line 1
+"#;
+
+fn load(display: &Display, path: &Path) -> Result<Program, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+
+    ShaderSrc {
+        vertex_shader: ScreenQuad::VERTEX_SHADER.to_string(),
+        fragment_shader: format!("{PRELUDE}{source}"),
+        geometry_shader: None,
+    }.compile(display).map_err(|error| format!("{}: {error}", path.display()))
+}
+
+/// Full-panel fragment shader loaded from disk, fed Shadertoy-style uniforms
+/// and an audio texture.
+pub struct ShaderProgram {
+    program: Program,
+    path: PathBuf,
+    display: Display,
+    quad: ScreenQuad,
+    audio: Texture2d,
+    /// Spectrum row followed by the waveform row, ready for upload.
+    rows: Vec<f32>,
+    spectrum: Vec<f32>,
+    start: Instant,
+    frame: i32,
+
+    beat: f32,
+    beat_phase: f32,
+    bands: [f32; 3],
+
+    scale: FrequencyScale,
+    nyquist: f32,
+    bin_width: f32,
+    reference: f32,
+}
+
+impl ShaderProgram {
+    pub fn new(display: &Display, settings: &ShaderSettings, sample_rate: usize, bin_width: f32, fft_window: usize) -> Result<Self, String> {
+        let program = load(display, &settings.path)?;
+
+        let audio = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32,
+            MipmapsOption::NoMipmap,
+            AUDIO_TEXTURE_WIDTH as u32,
+            2,
+        ).unwrap();
+
+        Ok(Self {
+            program,
+            path: settings.path.clone(),
+            display: display.clone(),
+            quad: ScreenQuad::new(display),
+            audio,
+            rows: vec![0.; AUDIO_TEXTURE_WIDTH * 2],
+            spectrum: vec![0.; AUDIO_TEXTURE_WIDTH],
+            start: Instant::now(),
+            frame: 0,
+
+            beat: 0.,
+            beat_phase: 0.,
+            bands: [0.; 3],
+
+            scale: settings.scale,
+            nyquist: sample_rate as f32 / 2.,
+            bin_width,
+            reference: spectrum_reference(fft_window),
+        })
+    }
+
+    /// Follows the processor to a new sample rate, keeping the history.
+    pub fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.nyquist = sample_rate as f32 / 2.;
+        self.bin_width = bin_width;
+    }
+
+    /// Reloads the shader if its file is in `changed`, keeping the current one
+    /// if the new version fails to load.
+    pub fn reload(&mut self, changed: &[PathBuf]) -> Result<(), String> {
+        if changed.iter().any(|path| path.ends_with(&self.path)) || LIBRARY.iter().any(|file| file.changed(changed)) {
+            self.program = load(&self.display, &self.path)?;
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, output: &ProcessorOutput) {
+        resample_spectrum(&output.left_fft, &mut self.spectrum, self.scale, self.nyquist, self.bin_width, self.reference);
+        self.rows[..AUDIO_TEXTURE_WIDTH].copy_from_slice(&self.spectrum);
+
+        // Stretch the waveform over the row if it is shorter than the texture
+        for (i, sample) in self.rows[AUDIO_TEXTURE_WIDTH..].iter_mut().enumerate() {
+            let source = i * output.waveform.len() / AUDIO_TEXTURE_WIDTH;
+            *sample = output.waveform.get(source).map_or(0.5, |s| s * 0.5 + 0.5);
+        }
+
+        self.audio.write(
+            Rect { left: 0, bottom: 0, width: AUDIO_TEXTURE_WIDTH as u32, height: 2 },
+            RawImage2d {
+                data: Cow::Borrowed(&self.rows),
+                width: AUDIO_TEXTURE_WIDTH as u32,
+                height: 2,
+                format: ClientFormat::F32,
+            },
+        );
+
+        self.beat = output.beat;
+        self.beat_phase = output.beat_phase;
+        self.bands = output.bands;
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect) {
+        let audio = Sampler::new(&self.audio)
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .magnify_filter(MagnifySamplerFilter::Linear);
+
+        self.quad.draw(
+            target,
+            &self.program,
+            &glium::uniform! {
+                iResolution: [viewport.width as f32, viewport.height as f32, 1.],
+                iTime: self.start.elapsed().as_secs_f32(),
+                iFrame: self.frame,
+                iBeat: self.beat,
+                iBeatPhase: self.beat_phase,
+                iBass: self.bands[0],
+                iMid: self.bands[1],
+                iTreble: self.bands[2],
+                iChannel0: audio,
+                viewport_offset: [viewport.left as f32, viewport.bottom as f32],
+            },
+            &glium::DrawParameters {
+                viewport: Some(viewport),
+                ..Default::default()
+            },
+        );
+
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,233 @@
+use std::{borrow::Cow, time::Duration};
+
+use glium::{
+    texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Frame,
+    Program,
+    Rect,
+    Texture2d,
+};
+
+use crate::graphics::{
+    programs::{
+        resample_spectrum,
+        spectrum_reference,
+        ScreenQuad,
+        ScrollClock,
+        ShaderSrc,
+    },
+    Display,
+};
+
+use crate::processing::FrequencyScale;
+
+#[derive(Clone, Copy, Debug)]
+pub enum ColourMap {
+    Viridis,
+    Magma,
+    Grayscale,
+}
+
+impl ColourMap {
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            ColourMap::Viridis => &[
+                [0x44, 0x01, 0x54], [0x48, 0x28, 0x78], [0x3e, 0x49, 0x89],
+                [0x31, 0x68, 0x8e], [0x26, 0x82, 0x8e], [0x1f, 0x9e, 0x89],
+                [0x35, 0xb7, 0x79], [0x6e, 0xce, 0x58], [0xfd, 0xe7, 0x25],
+            ],
+            ColourMap::Magma => &[
+                [0x00, 0x00, 0x04], [0x1c, 0x10, 0x44], [0x4f, 0x12, 0x7b],
+                [0x81, 0x25, 0x81], [0xb5, 0x36, 0x7a], [0xe5, 0x50, 0x64],
+                [0xfb, 0x87, 0x61], [0xfe, 0xc2, 0x87], [0xfc, 0xfd, 0xbf],
+            ],
+            ColourMap::Grayscale => &[[0, 0, 0], [0xff, 0xff, 0xff]],
+        }
+    }
+
+    /// 256 entry lookup table interpolated between the stops.
+    fn lut(&self) -> Vec<u8> {
+        let stops = self.stops();
+        let segments = (stops.len() - 1) as f32;
+
+        (0..256)
+            .flat_map(|i| {
+                let t = i as f32 / 255. * segments;
+                let j = (t as usize).min(stops.len() - 2);
+                let f = t - j as f32;
+                (0..3).map(move |c| (stops[j][c] as f32 * (1. - f) + stops[j + 1][c] as f32 * f).round() as u8)
+            })
+            .collect()
+    }
+}
+
+/// Direction the image scrolls in. Time runs along this axis and frequency
+/// along the other one.
+#[derive(Clone, Copy, Debug)]
+pub enum ScrollDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpectrogramSettings {
+    pub colour_map: ColourMap,
+    pub direction: ScrollDirection,
+    pub scale: FrequencyScale,
+    /// Length of history shown across the screen.
+    pub time_span: Duration,
+    /// Resolution of the history texture.
+    pub columns: usize,
+    pub rows: usize,
+}
+
+/// Scrolling waterfall of past spectra. Columns are written one at a time
+/// into a ring-buffered texture, and the shader unwraps the ring on screen.
+pub struct SpectrogramProgram {
+    program: Program,
+    quad: ScreenQuad,
+    history: Texture2d,
+    colour_map: Texture2d,
+    settings: SpectrogramSettings,
+
+    column: Vec<f32>,
+    head: usize,
+    clock: ScrollClock,
+
+    nyquist: f32,
+    bin_width: f32,
+    reference: f32,
+}
+
+impl SpectrogramProgram {
+    pub fn new(display: &Display, settings: SpectrogramSettings, sample_rate: usize, bin_width: f32, fft_window: usize) -> Self {
+        let program = ShaderSrc {
+            vertex_shader: ScreenQuad::VERTEX_SHADER.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec2 uv;
+                    out vec4 color;
+
+                    uniform sampler2D history;
+                    uniform sampler2D colour_map;
+                    uniform float head;
+                    uniform int direction;
+
+                    void main() {
+                        // x: 0 for the oldest column to 1 for the newest, y: frequency
+                        vec2 tf;
+                        if (direction == 0) {
+                            tf = uv;
+                        } else if (direction == 1) {
+                            tf = vec2(1.0 - uv.x, uv.y);
+                        } else if (direction == 2) {
+                            tf = vec2(1.0 - uv.y, uv.x);
+                        } else {
+                            tf = vec2(uv.y, uv.x);
+                        }
+
+                        float level = texture(history, vec2(fract(head + tf.x), tf.y)).r;
+                        color = texture(colour_map, vec2(level, 0.5));
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        }.get_program(display);
+
+        let history = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::F32,
+            MipmapsOption::NoMipmap,
+            settings.columns as u32,
+            settings.rows as u32,
+        ).unwrap();
+
+        let silence = vec![0f32; settings.columns * settings.rows];
+        history.write(
+            Rect { left: 0, bottom: 0, width: settings.columns as u32, height: settings.rows as u32 },
+            RawImage2d {
+                data: Cow::Borrowed(&silence),
+                width: settings.columns as u32,
+                height: settings.rows as u32,
+                format: ClientFormat::F32,
+            },
+        );
+
+        let colour_map = Texture2d::new(
+            display,
+            RawImage2d::from_raw_rgb(settings.colour_map.lut(), (256, 1)),
+        ).unwrap();
+
+        Self {
+            program,
+            quad: ScreenQuad::new(display),
+            history,
+            colour_map,
+            settings,
+
+            column: vec![0.; settings.rows],
+            head: 0,
+            clock: ScrollClock::new(settings.columns, settings.time_span),
+
+            nyquist: sample_rate as f32 / 2.,
+            bin_width,
+            reference: spectrum_reference(fft_window),
+        }
+    }
+
+    /// Follows the processor to a new sample rate, keeping the history.
+    pub fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.nyquist = sample_rate as f32 / 2.;
+        self.bin_width = bin_width;
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, values: &[f32]) {
+        let new_columns = self.clock.tick().min(self.settings.columns);
+
+        if new_columns > 0 {
+            resample_spectrum(values, &mut self.column, self.settings.scale, self.nyquist, self.bin_width, self.reference);
+        }
+
+        for _ in 0..new_columns {
+            self.history.write(
+                Rect { left: self.head as u32, bottom: 0, width: 1, height: self.settings.rows as u32 },
+                RawImage2d {
+                    data: Cow::Borrowed(&self.column),
+                    width: 1,
+                    height: self.settings.rows as u32,
+                    format: ClientFormat::F32,
+                },
+            );
+            self.head = (self.head + 1) % self.settings.columns;
+        }
+
+        let history = Sampler::new(&self.history)
+            .wrap_function(SamplerWrapFunction::Repeat)
+            .minify_filter(MinifySamplerFilter::Nearest)
+            .magnify_filter(MagnifySamplerFilter::Nearest);
+
+        let direction = match self.settings.direction {
+            ScrollDirection::Left => 0,
+            ScrollDirection::Right => 1,
+            ScrollDirection::Up => 2,
+            ScrollDirection::Down => 3,
+        };
+
+        self.quad.draw(
+            target,
+            &self.program,
+            &glium::uniform! {
+                history: history,
+                colour_map: Sampler::new(&self.colour_map).wrap_function(SamplerWrapFunction::Clamp),
+                head: self.head as f32 / self.settings.columns as f32,
+                direction: direction as i32,
+            },
+            &glium::DrawParameters {
+                viewport: Some(viewport),
+                ..Default::default()
+            },
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use glium::{implement_vertex, uniforms::Uniforms, Frame, IndexBuffer, Rect};
+
+use crate::graphics::{
+    camera::{Mat4, OrbitCamera},
+    programs::{
+        resample_spectrum,
+        spectrum_reference,
+        Decay,
+        ProgramRunner,
+        ScrollClock,
+        ShaderSrc,
+    },
+    theme::Gradient,
+    Display,
+};
+
+use crate::processing::FrequencyScale;
+
+#[derive(Default, Copy, Clone)]
+struct TerrainVertex {
+    height: f32,
+}
+implement_vertex!(TerrainVertex, height);
+
+// Rows move through the mesh as it scrolls, so heights are replaced rather
+// than smoothed like the line display
+impl Decay<f32> for TerrainVertex {
+    fn assign(&mut self, rhs: f32) {
+        self.height = rhs;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainSettings {
+    /// Number of past spectra in the mesh, newest at the back.
+    pub rows: usize,
+    /// Points across each spectrum.
+    pub columns: usize,
+    /// Time taken for a spectrum to travel from the back to the front.
+    pub time_span: Duration,
+    pub height: f32,
+    pub scale: FrequencyScale,
+}
+
+struct TerrainUniform {
+    view_projection: Mat4,
+    size: [i32; 2],
+    height: f32,
+    gradient: Gradient,
+    light: [f32; 3],
+}
+
+impl Uniforms for TerrainUniform {
+    fn visit_values<'a, F: FnMut(&str, glium::uniforms::UniformValue<'a>)>(&'a self, mut f: F) {
+        use glium::uniforms::UniformValue;
+
+        f("view_projection", UniformValue::Mat4(self.view_projection));
+        f("size", UniformValue::IntVec2(self.size));
+        f("height_scale", UniformValue::Float(self.height));
+        f("gradient_low", UniformValue::Vec3(self.gradient.low));
+        f("gradient_high", UniformValue::Vec3(self.gradient.high));
+        f("gradient_axis", UniformValue::SignedInt(self.gradient.axis as i32));
+        f("light", UniformValue::Vec3(self.light));
+    }
+}
+
+/// Height-mapped mesh of the last few spectra, scrolling towards the viewer
+/// and lit with flat shading. Viewed through an orbiting perspective camera.
+pub struct TerrainProgram {
+    prog: ProgramRunner<f32, TerrainVertex>,
+    indices: IndexBuffer<u32>,
+    uniforms: TerrainUniform,
+    settings: TerrainSettings,
+    pub camera: OrbitCamera,
+
+    /// Ring buffer of rows, `head` being the oldest.
+    history: Vec<f32>,
+    head: usize,
+    /// Rows unrolled from newest to oldest, as the mesh expects them.
+    ordered: Vec<f32>,
+    clock: ScrollClock,
+
+    nyquist: f32,
+    bin_width: f32,
+    reference: f32,
+}
+
+impl TerrainProgram {
+    pub fn new(display: &Display, settings: TerrainSettings, sample_rate: usize, bin_width: f32, fft_window: usize) -> Self {
+        let TerrainSettings { rows, columns, .. } = settings;
+
+        let shaders = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in float height;
+
+                    out vec3 worldGeo;
+                    out float levelGeo;
+
+                    uniform ivec2 size;
+                    uniform float height_scale;
+
+                    void main() {
+                        int column = gl_VertexID % size.x;
+                        int age = gl_VertexID / size.x;
+
+                        worldGeo = vec3(
+                            float(column) / float(size.x - 1) * 2.0 - 1.0,
+                            height * height_scale,
+                            float(age) / float(size.y - 1) * 2.0 - 1.0
+                        );
+                        levelGeo = height;
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec3 normalFrag;
+                    in float levelFrag;
+                    in float frequencyFrag;
+
+                    out vec4 color;
+
+                    uniform vec3 light;
+
+                    #include "gradient.glsl"
+
+                    void main() {
+                        float diffuse = max(dot(normalize(normalFrag), normalize(light)), 0.0);
+                        vec3 base = gradient(frequencyFrag, levelFrag);
+                        color = vec4(base * (0.25 + 0.75 * diffuse), 1.0);
+                    }
+                "#.to_string(),
+            geometry_shader: Some(r#"
+                    #version 330 core
+                    layout(triangles) in;
+                    layout(triangle_strip, max_vertices=3) out;
+
+                    in vec3 worldGeo[];
+                    in float levelGeo[];
+
+                    out vec3 normalFrag;
+                    out float levelFrag;
+                    out float frequencyFrag;
+
+                    uniform mat4 view_projection;
+
+                    void main() {
+                        // One normal per face gives the faceted look
+                        vec3 normal = normalize(cross(worldGeo[1] - worldGeo[0], worldGeo[2] - worldGeo[0]));
+                        normal = normal.y < 0.0 ? -normal : normal;
+
+                        for (int i = 0; i < 3; i++) {
+                            normalFrag = normal;
+                            levelFrag = levelGeo[i];
+                            frequencyFrag = worldGeo[i].x * 0.5 + 0.5;
+                            gl_Position = view_projection * vec4(worldGeo[i], 1.0);
+                            EmitVertex();
+                        }
+                        EndPrimitive();
+                    }
+                "#.to_string()),
+        };
+
+        let index_data: Vec<u32> = (0..rows - 1)
+            .flat_map(|r| (0..columns - 1).map(move |c| (r, c)))
+            .flat_map(|(r, c)| {
+                let i = (r * columns + c) as u32;
+                let below = i + columns as u32;
+                [i, i + 1, below, i + 1, below + 1, below]
+            })
+            .collect();
+
+        let mut prog = ProgramRunner::new(rows * columns, display, shaders);
+        prog.set_draw_parameters(glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        Self {
+            prog,
+            indices: IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &index_data).unwrap(),
+            uniforms: TerrainUniform {
+                view_projection: [[0.; 4]; 4],
+                size: [columns as i32, rows as i32],
+                height: settings.height,
+                gradient: Gradient::default(),
+                light: [0.3, 1., 0.5],
+            },
+            settings,
+            camera: OrbitCamera::default(),
+
+            history: vec![0.; rows * columns],
+            head: 0,
+            ordered: vec![0.; rows * columns],
+            clock: ScrollClock::new(rows, settings.time_span),
+
+            nyquist: sample_rate as f32 / 2.,
+            bin_width,
+            reference: spectrum_reference(fft_window),
+        }
+    }
+
+    /// Follows the processor to a new sample rate, keeping the history.
+    pub fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.nyquist = sample_rate as f32 / 2.;
+        self.bin_width = bin_width;
+    }
+
+    pub fn set_gradient(&mut self, gradient: Gradient) {
+        self.uniforms.gradient = gradient;
+    }
+
+    pub fn render(&mut self, target: &mut Frame, viewport: Rect, values: &[f32]) {
+        let TerrainSettings { rows, columns, .. } = self.settings;
+
+        let new_rows = self.clock.tick().min(rows);
+        if new_rows > 0 {
+            let row = self.head * columns;
+            resample_spectrum(
+                values,
+                &mut self.history[row..row + columns],
+                self.settings.scale,
+                self.nyquist,
+                self.bin_width,
+                self.reference,
+            );
+            for _ in 1..new_rows {
+                let next = (self.head + 1) % rows * columns;
+                self.history.copy_within(row..row + columns, next);
+                self.head = (self.head + 1) % rows;
+            }
+            self.head = (self.head + 1) % rows;
+
+            // Unroll the ring so the newest row comes first
+            for age in 0..rows {
+                let row = (self.head + rows - 1 - age) % rows * columns;
+                self.ordered[age * columns..(age + 1) * columns].copy_from_slice(&self.history[row..row + columns]);
+            }
+        }
+
+        self.uniforms.view_projection = self.camera.view_projection(viewport.width as f32 / viewport.height as f32);
+
+        self.prog.render(target, viewport, &self.ordered, &self.indices, &self.uniforms);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
+use glium::{
+    implement_vertex,
+    texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler},
+    Frame,
+    Program,
+    Rect,
+    Surface,
+    Texture2d,
+    VertexBuffer,
+};
+
+use crate::graphics::{programs::ShaderSrc, Display};
+
+/// DejaVu Sans Mono, so columns of numbers and key lists line up.
+const FONT: &[u8] = include_bytes!("../../../fonts/DejaVuSansMono.ttf");
+
+/// Side of the glyph atlas texture, in pixels.
+const ATLAS_SIZE: u32 = 1024;
+/// Empty pixels around each glyph in the atlas, so neighbours never bleed in.
+const PADDING: u32 = 1;
+
+#[derive(Copy, Clone)]
+struct TextVertex {
+    /// Window pixels from the bottom left.
+    pos: [f32; 2],
+    uv: [f32; 2],
+    colour: [f32; 4],
+}
+implement_vertex!(TextVertex, pos, uv, colour);
+
+/// Where a rasterised glyph sits in the atlas and relative to its origin.
+#[derive(Clone, Copy)]
+struct Glyph {
+    /// Atlas texture coordinates of the bottom left and top right.
+    uv: [f32; 4],
+    /// Pixel offsets of the left, top, right and bottom edges from the pen
+    /// position on the baseline, with y pointing down.
+    bounds: [f32; 4],
+}
+
+/// Bitmaps of every glyph drawn so far, packed into rows of a single texture.
+struct Atlas {
+    texture: Texture2d,
+    glyphs: HashMap<(GlyphId, u32), Option<Glyph>>,
+    /// Left and bottom of the free space in the current row.
+    cursor: (u32, u32),
+    row_height: u32,
+    /// Set when a glyph did not fit. The atlas is emptied after the frame.
+    full: bool,
+}
+
+impl Atlas {
+    fn new(display: &Display) -> Self {
+        let texture = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::U8,
+            MipmapsOption::NoMipmap,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+        ).unwrap();
+
+        let mut atlas = Self {
+            texture,
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+            full: false,
+        };
+        atlas.clear();
+        atlas
+    }
+
+    /// Empties the atlas, keeping only the solid texel used for boxes.
+    fn clear(&mut self) {
+        let zeros = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE) as usize];
+        self.write(0, 0, ATLAS_SIZE, ATLAS_SIZE, zeros);
+        self.write(0, 0, 2, 2, vec![255; 4]);
+
+        self.glyphs.clear();
+        self.cursor = (2 + PADDING, 0);
+        self.row_height = 2;
+        self.full = false;
+    }
+
+    fn write(&self, left: u32, bottom: u32, width: u32, height: u32, data: Vec<u8>) {
+        self.texture.write(
+            Rect { left, bottom, width, height },
+            RawImage2d {
+                data: Cow::Owned(data),
+                width,
+                height,
+                format: ClientFormat::U8,
+            },
+        );
+    }
+
+    /// Finds space for a `width` by `height` bitmap, starting a new row when
+    /// the current one is full.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + width > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.1 + height > ATLAS_SIZE || width > ATLAS_SIZE {
+            self.full = true;
+            return None;
+        }
+
+        let position = self.cursor;
+        self.cursor.0 += width + PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+
+    /// Rasterises a glyph at `size` pixels the first time it is asked for.
+    /// Glyphs with no outline, such as spaces, are `None`.
+    fn glyph(&mut self, font: &FontRef, id: GlyphId, size: u32) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&(id, size)) {
+            return *glyph;
+        }
+
+        let outlined = font.outline_glyph(id.with_scale(PxScale::from(size as f32)))?;
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+
+        let (left, bottom) = self.allocate(width, height)?;
+
+        // Rows flipped, as textures start at the bottom
+        let mut coverage = vec![0u8; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            if x < width && y < height {
+                coverage[((height - 1 - y) * width + x) as usize] = (c.clamp(0., 1.) * 255.) as u8;
+            }
+        });
+        self.write(left, bottom, width, height, coverage);
+
+        let texel = 1. / ATLAS_SIZE as f32;
+        let glyph = Some(Glyph {
+            uv: [
+                left as f32 * texel,
+                bottom as f32 * texel,
+                (left + width) as f32 * texel,
+                (bottom + height) as f32 * texel,
+            ],
+            bounds: [bounds.min.x, bounds.min.y, bounds.min.x + width as f32, bounds.min.y + height as f32],
+        });
+        self.glyphs.insert((id, size), glyph);
+        glyph
+    }
+}
+
+/// Draws text over the whole window from a glyph atlas. Text and boxes are
+/// queued from anywhere during a frame and all drawn by `draw`, in the order
+/// they were queued.
+pub struct TextProgram {
+    font: FontRef<'static>,
+    atlas: Atlas,
+    program: Program,
+    display: Display,
+    vertices: Vec<TextVertex>,
+}
+
+impl TextProgram {
+    pub fn new(display: &Display) -> Self {
+        let program = ShaderSrc {
+            vertex_shader: r#"
+                    #version 140
+                    in vec2 pos;
+                    in vec2 uv;
+                    in vec4 colour;
+
+                    out vec2 uvFrag;
+                    out vec4 colourFrag;
+
+                    uniform vec2 window_size;
+
+                    void main() {
+                        gl_Position = vec4(pos / window_size * 2.0 - 1.0, 0.0, 1.0);
+                        uvFrag = uv;
+                        colourFrag = colour;
+                    }
+                "#.to_string(),
+            fragment_shader: r#"
+                    #version 140
+                    in vec2 uvFrag;
+                    in vec4 colourFrag;
+
+                    out vec4 color;
+
+                    uniform sampler2D atlas;
+
+                    void main() {
+                        color = vec4(colourFrag.rgb, colourFrag.a * texture(atlas, uvFrag).r);
+                    }
+                "#.to_string(),
+            geometry_shader: None,
+        }.get_program(display);
+
+        Self {
+            font: FontRef::try_from_slice(FONT).unwrap(),
+            atlas: Atlas::new(display),
+            program,
+            display: display.clone(),
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Distance between baselines at `size` pixels.
+    pub fn line_height(&self, size: f32) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(size.round()));
+        (font.height() + font.line_gap()).ceil()
+    }
+
+    /// Width and height of `text` at `size` pixels. Lines are split at `\n`.
+    pub fn measure(&self, text: &str, size: f32) -> [f32; 2] {
+        let font = self.font.as_scaled(PxScale::from(size.round()));
+
+        let width = text.lines()
+            .map(|line| {
+                let mut previous = None;
+                line.chars().map(|c| {
+                    let id = font.glyph_id(c);
+                    let kern = previous.map_or(0., |previous| font.kern(previous, id));
+                    previous = Some(id);
+                    kern + font.h_advance(id)
+                }).sum::<f32>()
+            })
+            .fold(0., f32::max);
+        let lines = text.lines().count().max(1);
+
+        [width.ceil(), lines as f32 * self.line_height(size)]
+    }
+
+    fn quad(&mut self, [left, bottom, right, top]: [f32; 4], [u0, v0, u1, v1]: [f32; 4], colour: [f32; 4]) {
+        let v = |x, y, u, v| TextVertex { pos: [x, y], uv: [u, v], colour };
+        self.vertices.extend([
+            v(left, bottom, u0, v0), v(right, bottom, u1, v0), v(right, top, u1, v1),
+            v(left, bottom, u0, v0), v(right, top, u1, v1), v(left, top, u0, v1),
+        ]);
+    }
+
+    /// Queues a filled box between two corners, in window pixels from the
+    /// bottom left. Useful as a backdrop behind text.
+    pub fn rectangle(&mut self, min: [f32; 2], max: [f32; 2], colour: [f32; 4]) {
+        let solid = 1. / ATLAS_SIZE as f32;
+        self.quad([min[0], min[1], max[0], max[1]], [solid * 0.5, solid * 0.5, solid * 1.5, solid * 1.5], colour);
+    }
+
+    /// Queues `text` at `size` pixels. `position` is in window pixels from the
+    /// bottom left, and `anchor` picks the point of the text's box that lands
+    /// on it: `[0, 0]` for the bottom left, `[0.5, 0.5]` for the centre and
+    /// `[1, 1]` for the top right.
+    pub fn text(&mut self, text: &str, position: [f32; 2], size: f32, colour: [f32; 4], anchor: [f32; 2]) {
+        let pixels = size.round().max(1.) as u32;
+        let [width, height] = self.measure(text, size);
+        let line_height = self.line_height(size);
+
+        // Only borrows the bundled bytes, so cheap to copy
+        let unscaled = self.font.clone();
+        let font = unscaled.as_scaled(PxScale::from(pixels as f32));
+        let ascent = font.ascent();
+
+        // Whole pixels keep the atlas bitmaps sharp
+        let left = (position[0] - width * anchor[0]).round();
+        let top = (position[1] + height * (1. - anchor[1])).round();
+
+        for (row, line) in text.lines().enumerate() {
+            let baseline = (top - ascent - row as f32 * line_height).round();
+            let mut pen = left;
+            let mut previous = None;
+
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += font.kern(previous, id);
+                }
+                previous = Some(id);
+
+                if let Some(glyph) = self.atlas.glyph(&unscaled, id, pixels) {
+                    let [l, t, r, b] = glyph.bounds;
+                    let x = pen.round();
+                    self.quad([x + l, baseline - b, x + r, baseline - t], glyph.uv, colour);
+                }
+                pen += font.h_advance(id);
+            }
+        }
+    }
+
+    /// Draws and forgets everything queued since the last call.
+    pub fn draw(&mut self, target: &mut Frame) {
+        if !self.vertices.is_empty() {
+            let (width, height) = target.get_dimensions();
+            let vertex_buffer = VertexBuffer::new(&self.display, &self.vertices).unwrap();
+
+            target.draw(
+                &vertex_buffer,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &glium::uniform! {
+                    window_size: [width as f32, height as f32],
+                    atlas: Sampler::new(&self.atlas.texture)
+                        .minify_filter(MinifySamplerFilter::Nearest)
+                        .magnify_filter(MagnifySamplerFilter::Nearest),
+                },
+                &glium::DrawParameters {
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                },
+            ).unwrap();
+            self.vertices.clear();
+        }
+
+        // Glyphs that did not fit get drawn next frame, from an empty atlas
+        if self.atlas.full {
+            self.atlas.clear();
+        }
+    }
+}
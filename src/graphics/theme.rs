@@ -0,0 +1,152 @@
+/// Quantity a gradient is spread over. Passed to shaders as the
+/// `gradient_axis` uniform, which `gradient.glsl` compares against these
+/// values.
+#[derive(Clone, Copy, Debug)]
+pub enum GradientAxis {
+    /// Low to high frequencies.
+    Frequency = 0,
+    /// Quiet to loud.
+    Amplitude = 1,
+}
+
+/// Two-colour gradient interpolated in HSLuv, so lightness changes evenly
+/// along it. Colours are hue in degrees, then saturation and lightness from 0
+/// to 100. Hues are interpolated numerically, so 0 to 360 goes all the way
+/// round.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    pub low: [f32; 3],
+    pub high: [f32; 3],
+    pub axis: GradientAxis,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Theme::default().gradient
+    }
+}
+
+/// Named set of colours, applied to every visualisation at once. RGB colours
+/// are from 0 to 1.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub background: [f32; 3],
+    /// Left and right channel traces.
+    pub left: [f32; 3],
+    pub right: [f32; 3],
+    pub mid: [f32; 3],
+    pub side: [f32; 3],
+    /// Labels and overlays.
+    pub text: [f32; 3],
+    /// Fill of the bars and terrain.
+    pub gradient: Gradient,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            background: [0., 0., 0.],
+            left: [0.9, 0.9, 0.9],
+            right: [1.0, 0.2, 0.2],
+            mid: [0.2, 0.8, 0.2],
+            side: [0.2, 0.5, 1.0],
+            text: [0.8, 0.8, 0.8],
+            gradient: Gradient {
+                low: [265., 80., 30.],
+                high: [330., 80., 65.],
+                axis: GradientAxis::Amplitude,
+            },
+        }
+    }
+}
+
+/// Hue in degrees, saturation and value from 0 to 1.
+fn to_hsv([r, g, b]: [f32; 3]) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let range = max - r.min(g).min(b);
+
+    let hue = if range == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / range).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / range + 2.)
+    } else {
+        60. * ((r - g) / range + 4.)
+    };
+    let saturation = if max == 0. { 0. } else { range / max };
+
+    (hue, saturation, max)
+}
+
+fn from_hsv(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let channel = |n: f32| {
+        let k = (n + hue / 60.) % 6.;
+        value - value * saturation * k.min(4. - k).clamp(0., 1.)
+    };
+    [channel(5.), channel(3.), channel(1.)]
+}
+
+/// Hue buckets used to find the main colours of a picture.
+const HUE_BUCKETS: usize = 36;
+
+impl Theme {
+    /// Theme built around the two most prominent hues of an RGBA picture,
+    /// such as cover art. Returns `None` for pictures with hardly any colour.
+    /// HSV hues stand in for HSLuv ones in the gradient, which is close
+    /// enough for a palette.
+    pub fn from_picture(name: &str, pixels: &[u8]) -> Option<Self> {
+        let mut buckets = [0f32; HUE_BUCKETS];
+        let mut total = [0f32; 3];
+        let mut count = 0;
+
+        for pixel in pixels.chunks_exact(4) {
+            let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.);
+            total = [total[0] + rgb[0], total[1] + rgb[1], total[2] + rgb[2]];
+            count += 1;
+
+            // Weighted so vivid colours count for more than muddy ones
+            let (hue, saturation, value) = to_hsv(rgb);
+            if saturation > 0.2 && value > 0.2 {
+                buckets[(hue / 360. * HUE_BUCKETS as f32) as usize % HUE_BUCKETS] += saturation * value;
+            }
+        }
+
+        let bucket_hue = |bucket: usize| (bucket as f32 + 0.5) * 360. / HUE_BUCKETS as f32;
+        let (first, &weight) = buckets.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+        if count == 0 || weight < 0.01 * count as f32 {
+            return None;
+        }
+
+        // Second hue at least 40 degrees round the wheel from the first
+        let distance = |bucket: usize| {
+            let d = bucket.abs_diff(first);
+            d.min(HUE_BUCKETS - d)
+        };
+        let second = buckets.iter().enumerate()
+            .filter(|(bucket, weight)| distance(*bucket) >= 4 && **weight > 0.)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bucket, _)| bucket_hue(bucket))
+            .unwrap_or((bucket_hue(first) + 60.) % 360.);
+        let first = bucket_hue(first);
+
+        let average = total.map(|c| c / count as f32);
+
+        Some(Self {
+            name: name.to_string(),
+            background: average.map(|c| c * 0.15),
+            left: from_hsv(first, 0.5, 1.),
+            right: from_hsv(second, 0.6, 1.),
+            mid: from_hsv(first, 0.3, 1.),
+            side: from_hsv(second, 0.8, 0.9),
+            text: from_hsv(first, 0.15, 0.95),
+            gradient: Gradient {
+                low: [first, 85., 35.],
+                high: [second, 85., 75.],
+                axis: GradientAxis::Amplitude,
+            },
+        })
+    }
+}
@@ -0,0 +1,779 @@
+use std::path::PathBuf;
+
+use glium::{Frame, Rect};
+
+use crate::{
+    graphics::{
+        camera::OrbitCamera,
+        programs::{
+            artprogram::{ArtProgram, ArtSettings},
+            barsprogram::{BarSettings, BarsProgram},
+            correlationprogram::CorrelationProgram,
+            fftprogram::{FFTProgram, GridSettings, SpectrumAxes},
+            textprogram::TextProgram,
+            meterprogram::{MeterProgram, MeterStyle},
+            peakprogram::PeakProgram,
+            phaseprogram::{PhaseProgram, PhaseSettings},
+            postprogram::Effect,
+            radialprogram::{RadialProgram, RadialSettings},
+            scopeprogram::{ScopeProgram, ScopeStyle},
+            shaderprogram::{ShaderProgram, ShaderSettings},
+            spectrogramprogram::{SpectrogramProgram, SpectrogramSettings},
+            terrainprogram::{TerrainProgram, TerrainSettings},
+        },
+        theme::Theme,
+        Display,
+    },
+    metadata::Metadata,
+    processing::{peak::PeakLevels, scope::ScopeSettings, PhaseVertex, Processor, ProcessorOutput},
+};
+
+/// Something that can be drawn into a layout panel. `update` is called once
+/// per frame with the latest analysis, before `draw`.
+pub trait Visualisation {
+    fn update(&mut self, output: &ProcessorOutput);
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect);
+
+    /// Called whenever the panel's viewport changes size.
+    fn resize(&mut self, _viewport: Rect) {}
+
+    /// Camera driven by the mouse and arrow keys, for 3D displays.
+    fn camera(&mut self) -> Option<&mut OrbitCamera> {
+        None
+    }
+
+    /// Switches to the next way of showing the same data, if there is one.
+    fn next_mode(&mut self) {}
+
+    /// Takes its colours from `theme`. Called once after construction and
+    /// again whenever the theme changes.
+    fn set_theme(&mut self, _theme: &Theme) {}
+
+    /// Called once after construction and again whenever the track changes.
+    fn set_track(&mut self, _track: &Metadata) {}
+
+    /// Called when the processor switches to a new sample rate, making its
+    /// FFT bins `bin_width` Hz wide.
+    fn set_sample_rate(&mut self, _sample_rate: usize, _bin_width: f32) {}
+
+    /// Applies new spectrum gridline settings.
+    fn set_grid(&mut self, _grid: GridSettings) {}
+
+    /// Queues any text to draw over the panel, after every panel is drawn.
+    /// `cursor` is the mouse position in window pixels from the bottom left,
+    /// while it is over the panel.
+    fn label(&self, _text: &mut TextProgram, _viewport: Rect, _cursor: Option<[f32; 2]>) {}
+
+    /// Rebuilds any shader read from one of the `changed` files. On failure
+    /// the previous shader stays in use.
+    fn reload(&mut self, _changed: &[PathBuf]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Settings for every built-in visualisation. Only the ones named in the
+/// layout are built.
+#[derive(Clone, Debug)]
+pub struct ProgramSettings {
+    pub meter_style: MeterStyle,
+    pub phase: PhaseSettings,
+    pub spectrogram: SpectrogramSettings,
+    pub bars: BarSettings,
+    pub radial: RadialSettings,
+    pub terrain: TerrainSettings,
+    pub scope: ScopeStyle,
+    /// User shaders, registered under their own names.
+    pub shaders: Vec<ShaderSettings>,
+    /// Themes to cycle through, starting with the first. The default theme is
+    /// used if there are none.
+    pub themes: Vec<Theme>,
+    /// Thickness of the spectrum and mid/side lines, in pixels.
+    pub line_width: f32,
+    /// Effects applied to the whole frame after the panels, in order.
+    pub post_process: Vec<Effect>,
+    pub art: ArtSettings,
+    /// Gridlines over the spectrum panels.
+    pub grid: GridSettings,
+    /// Height of labels and overlay text, in pixels.
+    pub text_size: f32,
+    /// Shows the track, position and frame rate in the top left corner.
+    pub hud: bool,
+    /// Switches to a theme taken from the cover art of each track that has
+    /// some.
+    pub art_theme: bool,
+}
+
+/// Builds a visualisation, or says why it cannot be built, such as the
+/// processor being set up without the data it needs.
+pub type Constructor = Box<dyn Fn(&Display, &ProgramSettings, &Processor) -> Result<Box<dyn Visualisation>, String>>;
+
+/// Visualisations by name, as used in the layout.
+pub struct Registry {
+    constructors: Vec<(String, Constructor)>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self { constructors: Vec::new() };
+
+        registry.register("spectrum", |display, settings, processor| Ok(Box::new(Spectrum::new(display, settings, processor))));
+        registry.register("mid_side", |display, settings, processor| {
+            processor.mid_side()
+                .then(|| Box::new(MidSide::new(display, settings, processor)) as Box<dyn Visualisation>)
+                .ok_or_else(|| "mid/side analysis is off".to_string())
+        });
+        registry.register("phase", |display, settings, processor| Ok(Box::new(Phase {
+            program: PhaseProgram::new(processor.phase_pts, display, settings.phase),
+            values: Vec::new(),
+        })));
+        registry.register("scope", |display, settings, processor| {
+            processor.scope_settings().zip(processor.scope_window()).map(|(scope, size)| Box::new(Scope {
+                program: ScopeProgram::new(size, display, settings.scope),
+                display: display.clone(),
+                settings: scope,
+                buffer_len: processor.fft_window(),
+                values: (Vec::new(), Vec::new()),
+            }) as Box<dyn Visualisation>).ok_or_else(|| "the scope is off".to_string())
+        });
+        registry.register("correlation", |display, _, _| Ok(Box::new(Correlation {
+            program: CorrelationProgram::new(display),
+            values: (0., 0.),
+        })));
+        registry.register("peaks", |display, _, _| Ok(Box::new(Peaks {
+            program: PeakProgram::new(display),
+            values: (PeakLevels::default(), PeakLevels::default()),
+        })));
+        registry.register("levels", |display, settings, _| Ok(Box::new(Levels {
+            program: MeterProgram::new(display, settings.meter_style, -18.),
+            values: (0., 0.),
+        })));
+        registry.register("spectrogram", |display, settings, processor| Ok(Box::new(Spectrogram {
+            program: SpectrogramProgram::new(
+                display,
+                settings.spectrogram,
+                processor.sample_rate(),
+                processor.bin_width(),
+                processor.fft_window(),
+            ),
+            values: Vec::new(),
+        })));
+        registry.register("bars", |display, settings, processor| Ok(Box::new(Bars {
+            program: BarsProgram::new(display, settings.bars, processor.fft_window()),
+            values: Vec::new(),
+        })));
+        registry.register("radial", |display, settings, processor| Ok(Box::new(Radial {
+            program: RadialProgram::new(processor.fft_output_bins, display, settings.radial, processor.fft_window()),
+            values: Vec::new(),
+            beat: 0.,
+        })));
+        registry.register("terrain", |display, settings, processor| Ok(Box::new(Terrain {
+            program: TerrainProgram::new(
+                display,
+                settings.terrain,
+                processor.sample_rate(),
+                processor.bin_width(),
+                processor.fft_window(),
+            ),
+            values: Vec::new(),
+        })));
+
+        registry
+    }
+}
+
+impl Registry {
+    /// Registers the built-ins, every user shader in `settings`, then the
+    /// cover art.
+    pub fn new(settings: &ProgramSettings) -> Self {
+        let mut registry = Self::default();
+
+        for shader in &settings.shaders {
+            let name = shader.name.clone();
+            let shader = shader.clone();
+            registry.register(&name, move |display, _, processor| {
+                let program = ShaderProgram::new(display, &shader, processor.sample_rate(), processor.bin_width(), processor.fft_window())?;
+                Ok(Box::new(UserShader { program }))
+            });
+        }
+
+        // Last, so panels registered before it keep their keys
+        registry.register("art", |display, settings, _| Ok(Box::new(Cover {
+            program: ArtProgram::new(display, settings.art),
+            caption: String::new(),
+            size: settings.text_size,
+            colour: [1.; 4],
+        })));
+
+        registry
+    }
+
+    /// Adds a visualisation, replacing any existing one with the same name.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(&Display, &ProgramSettings, &Processor) -> Result<Box<dyn Visualisation>, String> + 'static,
+    {
+        self.constructors.retain(|(existing, _)| existing != name);
+        self.constructors.push((name.to_string(), Box::new(constructor)));
+    }
+
+    /// Registered names, in registration order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.constructors.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn build(&self, name: &str, display: &Display, settings: &ProgramSettings, processor: &Processor) -> Result<Box<dyn Visualisation>, String> {
+        let (_, constructor) = self.constructors.iter()
+            .find(|(existing, _)| existing == name)
+            .ok_or_else(|| "no visualisation by that name".to_string())?;
+        constructor(display, settings, processor)
+    }
+}
+
+/// Frequency and level labels along the edges of a spectrum, optional
+/// gridlines, and a readout of the bins under the mouse.
+struct SpectrumOverlay {
+    axes: SpectrumAxes,
+    grid: GridSettings,
+    size: f32,
+    colour: [f32; 3],
+}
+
+/// Label steps tried along the frequency axis, smallest first.
+const FREQUENCY_STEPS: [f32; 8] = [100., 200., 500., 1000., 2000., 5000., 10000., 20000.];
+/// Label steps tried along the level axis, in dB.
+const LEVEL_STEPS: [i32; 5] = [10, 20, 30, 40, 60];
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+fn format_frequency(frequency: f32) -> String {
+    match frequency {
+        f if f < 1000. && f.fract() != 0. => format!("{f:.1}"),
+        f if f < 1000. => format!("{f:.0}"),
+        f if f % 1000. == 0. => format!("{:.0}k", f / 1000.),
+        f => format!("{:.1}k", f / 1000.),
+    }
+}
+
+/// Nearest equal tempered note to `frequency`, tuned to A4 = 440 Hz, and how
+/// far off it is in cents.
+fn note_name(frequency: f32) -> String {
+    let semitones = 69. + 12. * (frequency / 440.).log2();
+    let note = semitones.round();
+    let cents = ((semitones - note) * 100.).round() + 0.;
+    let name = NOTE_NAMES[(note as i32).rem_euclid(12) as usize];
+    format!("{name}{} {cents:+.0}c", (note as i32).div_euclid(12) - 1)
+}
+
+impl SpectrumOverlay {
+    fn new(settings: &ProgramSettings, processor: &Processor) -> Self {
+        Self {
+            axes: SpectrumAxes::new(processor.fft_output_bins, processor.bin_width(), processor.fft_window()),
+            grid: settings.grid,
+            size: settings.text_size,
+            colour: [1.; 3],
+        }
+    }
+
+    fn colour(&self, alpha: f32) -> [f32; 4] {
+        let [r, g, b] = self.colour;
+        [r, g, b, alpha]
+    }
+
+    /// Window pixel position of a point in the viewport's normalised device
+    /// coordinates.
+    fn pixel(viewport: Rect, [x, y]: [f32; 2]) -> [f32; 2] {
+        [
+            viewport.left as f32 + (x + 1.) / 2. * viewport.width as f32,
+            viewport.bottom as f32 + (y + 1.) / 2. * viewport.height as f32,
+        ]
+    }
+
+    /// Queues the labels, any gridlines, and the readout of `traces` when
+    /// the cursor is over the spectrum.
+    fn queue(&self, text: &mut TextProgram, viewport: Rect, cursor: Option<[f32; 2]>, traces: [(&str, &[f32]); 2]) {
+        let max = self.axes.max_frequency();
+        let [left, bottom] = Self::pixel(viewport, [self.axes.x(0.), -1.]);
+        let [right, top] = Self::pixel(viewport, [self.axes.x(max), 1.]);
+        let x = |frequency: f32| Self::pixel(viewport, [self.axes.x(frequency), 0.])[0];
+        let y = |db: f32| Self::pixel(viewport, [0., self.axes.y(db)])[1];
+
+        // Gridlines first, so labels sit on top
+        let frequencies = self.grid.frequencies.map(|grid| grid.lines(max)).unwrap_or_default();
+        for &(frequency, major) in &frequencies {
+            let alpha = if major { self.grid.opacity } else { self.grid.opacity * 0.4 };
+            let x = x(frequency).round();
+            text.rectangle([x, bottom], [x + 1., top], self.colour(alpha));
+        }
+        if let Some(step) = self.grid.db_step.filter(|&step| step > 0.) {
+            for db in (0..).map(|i| i as f32 * -step).take_while(|&db| self.axes.y(db) >= -1.) {
+                if self.axes.y(db) <= 1. {
+                    let y = y(db).round();
+                    text.rectangle([left, y], [right, y + 1.], self.colour(self.grid.opacity));
+                }
+            }
+        }
+
+        // Frequency labels on the major gridlines, or on the smallest step
+        // that leaves room between them
+        let room = text.measure("00.0k", self.size)[0] * 1.5;
+        let labelled: Vec<f32> = match self.grid.frequencies {
+            Some(_) => frequencies.iter().filter(|(_, major)| *major).map(|&(frequency, _)| frequency).collect(),
+            None => {
+                let step = FREQUENCY_STEPS.into_iter()
+                    .find(|&step| x(step) - x(0.) >= room)
+                    .unwrap_or(FREQUENCY_STEPS[FREQUENCY_STEPS.len() - 1]);
+                (1..).map(|i| i as f32 * step).take_while(|&f| f < max).collect()
+            },
+        };
+        let mut last = f32::MIN;
+        for frequency in labelled {
+            let x = x(frequency);
+            if x - last >= room {
+                text.text(&format_frequency(frequency), [x, bottom + 2.], self.size, self.colour(0.7), [0.5, 0.]);
+                last = x;
+            }
+        }
+
+        // Level labels on the gridlines, or on the smallest step that leaves
+        // room between them
+        let room = text.line_height(self.size) * 2.;
+        let step = match self.grid.db_step.filter(|&step| step > 0.) {
+            Some(step) => step,
+            None => LEVEL_STEPS.into_iter()
+                .find(|&step| y(0.) - y(-step as f32) >= room)
+                .unwrap_or(LEVEL_STEPS[LEVEL_STEPS.len() - 1]) as f32,
+        };
+        let mut last = f32::MAX;
+        for db in (0..).map(|i| i as f32 * -step) {
+            if self.axes.y(db) < -0.9 {
+                break;
+            }
+            let y = y(db);
+            if self.axes.y(db) <= 1. && last - y >= room {
+                // Adding zero turns -0 into 0
+                text.text(&format!("{} dB", db + 0.), [viewport.left as f32 + 4., y], self.size, self.colour(0.7), [0., 0.5]);
+                last = y;
+            }
+        }
+
+        if let Some(cursor) = cursor {
+            self.readout(text, viewport, cursor, traces);
+        }
+    }
+
+    /// Frequency, nearest note and levels of the bins under `cursor`, beside
+    /// a line marking its position.
+    fn readout(&self, text: &mut TextProgram, viewport: Rect, [x, y]: [f32; 2], traces: [(&str, &[f32]); 2]) {
+        let ndc = (x - viewport.left as f32) / viewport.width as f32 * 2. - 1.;
+        let frequency = self.axes.frequency(ndc);
+        if !(0. ..=self.axes.max_frequency()).contains(&frequency) {
+            return;
+        }
+
+        let bin = self.axes.bin(frequency);
+        let levels = traces.iter()
+            .filter_map(|(name, values)| values.get(bin).map(|&value| format!("{name} {:.1} dB", self.axes.level(value))))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let readout = format!("{frequency:.1} Hz  {}\n{levels}", note_name(frequency));
+
+        let top = (viewport.bottom + viewport.height) as f32;
+        text.rectangle([x.round(), viewport.bottom as f32], [x.round() + 1., top], self.colour(0.5));
+
+        // Kept on the side of the cursor with more room
+        let [width, height] = text.measure(&readout, self.size);
+        let margin = self.size * 0.5;
+        let centre = viewport.left as f32 + viewport.width as f32 / 2.;
+        let anchor = [if x > centre { 1. } else { 0. }, 0.];
+        let offset = if x > centre { -self.size } else { self.size };
+        let position = [x + offset, y.min(top - height - 2. * margin) + margin];
+
+        let box_left = position[0] - width * anchor[0];
+        text.rectangle(
+            [box_left - margin, position[1] - margin],
+            [box_left + width + margin, position[1] + height + margin],
+            [0., 0., 0., 0.6],
+        );
+        text.text(&readout, position, self.size, self.colour(1.), anchor);
+    }
+}
+
+/// Left and right spectra as lines.
+struct Spectrum {
+    left: FFTProgram,
+    right: FFTProgram,
+    overlay: SpectrumOverlay,
+    values: (Vec<f32>, Vec<f32>),
+}
+
+impl Spectrum {
+    fn new(display: &Display, settings: &ProgramSettings, processor: &Processor) -> Self {
+        Self {
+            left: FFTProgram::new(processor.fft_output_bins, display, settings.line_width),
+            right: FFTProgram::new(processor.fft_output_bins, display, settings.line_width),
+            overlay: SpectrumOverlay::new(settings, processor),
+            values: (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+impl Visualisation for Spectrum {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.0.clone_from(&output.left_fft);
+        self.values.1.clone_from(&output.right_fft);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.left.render(target, viewport, &self.values.0);
+        self.right.render(target, viewport, &self.values.1);
+    }
+
+    fn label(&self, text: &mut TextProgram, viewport: Rect, cursor: Option<[f32; 2]>) {
+        self.overlay.queue(text, viewport, cursor, [("L", &self.values.0), ("R", &self.values.1)]);
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: usize, bin_width: f32) {
+        self.overlay.axes.set_bin_width(bin_width);
+    }
+
+    fn set_grid(&mut self, grid: GridSettings) {
+        self.overlay.grid = grid;
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.left.set_colour(theme.left);
+        self.right.set_colour(theme.right);
+        self.overlay.colour = theme.text;
+    }
+
+    fn reload(&mut self, changed: &[PathBuf]) -> Result<(), String> {
+        self.left.reload(changed).map_err(|error| error.to_string())?;
+        self.right.reload(changed).map_err(|error| error.to_string())
+    }
+}
+
+/// Mid and side spectra as lines.
+struct MidSide {
+    mid: FFTProgram,
+    side: FFTProgram,
+    overlay: SpectrumOverlay,
+    values: (Vec<f32>, Vec<f32>),
+}
+
+impl MidSide {
+    fn new(display: &Display, settings: &ProgramSettings, processor: &Processor) -> Self {
+        Self {
+            mid: FFTProgram::new(processor.fft_output_bins, display, settings.line_width),
+            side: FFTProgram::new(processor.fft_output_bins, display, settings.line_width),
+            overlay: SpectrumOverlay::new(settings, processor),
+            values: (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+impl Visualisation for MidSide {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.0.clone_from(&output.mid_fft);
+        self.values.1.clone_from(&output.side_fft);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.mid.render(target, viewport, &self.values.0);
+        self.side.render(target, viewport, &self.values.1);
+    }
+
+    fn label(&self, text: &mut TextProgram, viewport: Rect, cursor: Option<[f32; 2]>) {
+        self.overlay.queue(text, viewport, cursor, [("M", &self.values.0), ("S", &self.values.1)]);
+    }
+
+    fn set_sample_rate(&mut self, _sample_rate: usize, bin_width: f32) {
+        self.overlay.axes.set_bin_width(bin_width);
+    }
+
+    fn set_grid(&mut self, grid: GridSettings) {
+        self.overlay.grid = grid;
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.mid.set_colour(theme.mid);
+        self.side.set_colour(theme.side);
+        self.overlay.colour = theme.text;
+    }
+
+    fn reload(&mut self, changed: &[PathBuf]) -> Result<(), String> {
+        self.mid.reload(changed).map_err(|error| error.to_string())?;
+        self.side.reload(changed).map_err(|error| error.to_string())
+    }
+}
+
+struct Phase {
+    program: PhaseProgram,
+    values: Vec<PhaseVertex>,
+}
+
+impl Visualisation for Phase {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.clone_from(&output.phase_left);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values);
+    }
+
+    fn resize(&mut self, _viewport: Rect) {
+        self.program.clear_persistence();
+    }
+
+    fn next_mode(&mut self) {
+        self.program.next_mode();
+    }
+
+    fn reload(&mut self, changed: &[PathBuf]) -> Result<(), String> {
+        self.program.reload(changed).map_err(|error| error.to_string())
+    }
+}
+
+struct Scope {
+    program: ScopeProgram,
+    display: Display,
+    /// Used to work the window out again when the sample rate changes.
+    settings: ScopeSettings,
+    buffer_len: usize,
+    values: (Vec<f32>, Vec<f32>),
+}
+
+impl Visualisation for Scope {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.0.clone_from(&output.scope.0);
+        self.values.1.clone_from(&output.scope.1);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values.0, &self.values.1);
+    }
+
+    fn next_mode(&mut self) {
+        self.program.next_mode();
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.program.set_colours(theme.left, theme.right);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: usize, _bin_width: f32) {
+        let window = self.settings.window(sample_rate, self.buffer_len);
+        self.program.set_window(&self.display, window);
+    }
+}
+
+struct Correlation {
+    program: CorrelationProgram,
+    values: (f32, f32),
+}
+
+impl Visualisation for Correlation {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values = (output.correlation, output.balance);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, self.values.0, self.values.1);
+    }
+}
+
+struct Peaks {
+    program: PeakProgram,
+    values: (PeakLevels, PeakLevels),
+}
+
+impl Visualisation for Peaks {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values = (output.left_peak, output.right_peak);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values.0, &self.values.1);
+    }
+}
+
+struct Levels {
+    program: MeterProgram,
+    values: (f32, f32),
+}
+
+impl Visualisation for Levels {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values = (output.left_level, output.right_level);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, self.values.0, self.values.1);
+    }
+}
+
+struct Spectrogram {
+    program: SpectrogramProgram,
+    values: Vec<f32>,
+}
+
+impl Visualisation for Spectrogram {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.clone_from(&output.left_fft);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.program.set_sample_rate(sample_rate, bin_width);
+    }
+}
+
+struct Bars {
+    program: BarsProgram,
+    values: Vec<f32>,
+}
+
+impl Visualisation for Bars {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.clone_from(&output.left_fft);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values);
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.program.set_gradient(theme.gradient);
+    }
+}
+
+struct Radial {
+    program: RadialProgram,
+    values: Vec<f32>,
+    beat: f32,
+}
+
+impl Visualisation for Radial {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.clone_from(&output.left_fft);
+        self.beat = output.beat;
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values, self.beat);
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.program.set_colour(theme.left);
+    }
+}
+
+struct Terrain {
+    program: TerrainProgram,
+    values: Vec<f32>,
+}
+
+impl Visualisation for Terrain {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.values.clone_from(&output.left_fft);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport, &self.values);
+    }
+
+    fn camera(&mut self) -> Option<&mut OrbitCamera> {
+        Some(&mut self.program.camera)
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.program.set_sample_rate(sample_rate, bin_width);
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        self.program.set_gradient(theme.gradient);
+    }
+}
+
+struct UserShader {
+    program: ShaderProgram,
+}
+
+impl Visualisation for UserShader {
+    fn update(&mut self, output: &ProcessorOutput) {
+        self.program.update(output);
+    }
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: usize, bin_width: f32) {
+        self.program.set_sample_rate(sample_rate, bin_width);
+    }
+
+    fn reload(&mut self, changed: &[PathBuf]) -> Result<(), String> {
+        self.program.reload(changed)
+    }
+}
+
+/// Cover art with the title, artist and album beside it.
+struct Cover {
+    program: ArtProgram,
+    caption: String,
+    size: f32,
+    colour: [f32; 4],
+}
+
+impl Visualisation for Cover {
+    fn update(&mut self, _output: &ProcessorOutput) {}
+
+    fn draw(&mut self, target: &mut Frame, viewport: Rect) {
+        self.program.render(target, viewport);
+    }
+
+    fn label(&self, text: &mut TextProgram, viewport: Rect, _cursor: Option<[f32; 2]>) {
+        let area = self.program.area(viewport);
+        let gap = if area.width > 0 { self.size } else { 0. };
+        let position = [(area.left + area.width) as f32 + gap, area.bottom as f32];
+        text.text(&self.caption, position, self.size, self.colour, [0., 0.]);
+    }
+
+    fn set_theme(&mut self, theme: &Theme) {
+        let [r, g, b] = theme.text;
+        self.colour = [r, g, b, 1.];
+    }
+
+    fn set_track(&mut self, track: &Metadata) {
+        self.program.set_art(track.art.as_ref());
+        self.caption = [&track.title, &track.artist, &track.album]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_notes_in_tune() {
+        assert_eq!(note_name(440.), "A4 +0c");
+        assert_eq!(note_name(261.63), "C4 +0c");
+        assert_eq!(note_name(27.5), "A0 +0c");
+    }
+
+    #[test]
+    fn names_the_nearest_note_and_its_offset() {
+        assert_eq!(note_name(445.), "A4 +20c");
+        assert_eq!(note_name(430.), "A4 -40c");
+        // Over half a semitone sharp of G4 is a flat G#4
+        assert_eq!(note_name(410.), "G#4 -22c");
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Watches shader directories for changes, so programs can be rebuilt while
+/// running.
+pub struct ShaderWatcher {
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderWatcher {
+    /// Watches each of `directories` that can be watched, warning about the
+    /// rest, such as a missing `shaders` directory when running with the
+    /// embedded shaders.
+    pub fn new<'a>(directories: impl IntoIterator<Item = &'a Path>) -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+
+        for directory in directories {
+            if let Err(error) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                eprintln!("Not watching {} for shader changes: {error}", directory.display());
+            }
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Files created or modified since the last call, without duplicates.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = Vec::new();
+
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        if !changed.contains(&path) {
+                            changed.push(path);
+                        }
+                    }
+                },
+                Ok(_) => (),
+                Err(error) => eprintln!("Shader watcher: {error}"),
+            }
+        }
+
+        changed
+    }
+}
@@ -4,24 +4,214 @@
 #![feature(duration_constructors)]
 #![feature(isqrt)]
 
+use std::{path::PathBuf, time::Duration};
+
 use glium::winit;
 
 mod graphics;
 mod processing;
 mod audio;
+mod metadata;
+mod playlist;
+
+/// Builds the playlist from the command line: any mix of audio files, M3U or
+/// PLS playlists and directories, with `--shuffle`, `--repeat` and
+/// `--repeat-one`.
+fn playlist() -> playlist::Playlist {
+    let mut paths = Vec::new();
+    let mut shuffle = false;
+    let mut repeat = playlist::Repeat::Off;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--shuffle" => shuffle = true,
+            "--repeat" => repeat = playlist::Repeat::All,
+            "--repeat-one" => repeat = playlist::Repeat::One,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("music.mp3".into());
+    }
+
+    playlist::Playlist::new(paths, shuffle, repeat)
+}
 
 fn main() {
+    let playlist = playlist();
+    if playlist.is_empty() {
+        eprintln!("Nothing to play");
+        std::process::exit(1);
+    }
+
     let event_loop = winit::event_loop::EventLoop::builder().build().unwrap();
 
-    let mut app = graphics::App::new(
+    let mut keymap = graphics::KeyMap::default();
+    keymap.bind(winit::keyboard::Key::Character("s".into()), graphics::Action::Screenshot);
+
+    let app = graphics::App::new(
         "Nyoom",
         800, 
         600, 
         165., 
-        "music.mp3", 
+        4,
+        playlist,
         8192,
         4192,
-    400);
+        400,
+        processing::peak::PeakSettings::new(
+            Duration::from_secs(2),
+            Duration::from_secs_f32(1.7),
+        ),
+        processing::meter::MeterType::Vu,
+        Duration::from_millis(300),
+        true,
+        Some(processing::scope::ScopeSettings {
+            timebase: Duration::from_millis(20),
+            trigger: Some(processing::scope::Trigger {
+                level: 0.,
+                hysteresis: 0.05,
+            }),
+        }),
+        graphics::ProgramSettings {
+            meter_style: graphics::MeterStyle::Needle,
+            phase: graphics::PhaseSettings {
+                mode: graphics::PhaseMode::Goniometer,
+                gain: graphics::PhaseGain::Auto,
+                persistence: Some(Duration::from_millis(150)),
+                width: 1.5,
+            },
+            spectrogram: graphics::SpectrogramSettings {
+                colour_map: graphics::ColourMap::Magma,
+                direction: graphics::ScrollDirection::Left,
+                scale: processing::FrequencyScale::Log,
+                time_span: Duration::from_secs(10),
+                columns: 1024,
+                rows: 512,
+            },
+            bars: graphics::BarSettings {
+                count: 64,
+                gap: 0.2,
+                rounded: true,
+                mirror: false,
+                peak_hold: Duration::from_millis(500),
+                peak_gravity: 2.,
+            },
+            radial: graphics::RadialSettings {
+                inner_radius: 0.3,
+                length: 0.4,
+                rotation: std::f32::consts::FRAC_PI_2,
+                mirror: true,
+                pulse: 0.2,
+            },
+            terrain: graphics::TerrainSettings {
+                rows: 128,
+                columns: 256,
+                time_span: Duration::from_secs(8),
+                height: 0.6,
+                scale: processing::FrequencyScale::Log,
+            },
+            scope: graphics::ScopeStyle {
+                mode: graphics::ScopeMode::Channels,
+                width: 2.,
+            },
+            shaders: vec![graphics::ShaderSettings {
+                name: "rings".into(),
+                path: "shaders/rings.frag".into(),
+                scale: processing::FrequencyScale::Log,
+            }],
+            themes: vec![
+                graphics::Theme::default(),
+                graphics::Theme {
+                    name: "ember".into(),
+                    background: [0.05, 0.02, 0.02],
+                    left: [1.0, 0.6, 0.2],
+                    right: [1.0, 0.3, 0.3],
+                    mid: [1.0, 0.8, 0.4],
+                    side: [0.8, 0.3, 0.6],
+                    text: [1.0, 0.85, 0.7],
+                    gradient: graphics::Gradient {
+                        low: [10., 90., 25.],
+                        high: [60., 90., 85.],
+                        axis: graphics::GradientAxis::Amplitude,
+                    },
+                },
+                graphics::Theme {
+                    name: "spectrum".into(),
+                    background: [0.02, 0.02, 0.05],
+                    left: [0.3, 1.0, 0.4],
+                    right: [1.0, 0.8, 0.3],
+                    mid: [0.4, 0.9, 1.0],
+                    side: [1.0, 0.4, 0.8],
+                    text: [0.85, 0.9, 1.0],
+                    gradient: graphics::Gradient {
+                        low: [0., 85., 60.],
+                        high: [300., 85., 60.],
+                        axis: graphics::GradientAxis::Frequency,
+                    },
+                },
+            ],
+            line_width: 1.5,
+            post_process: vec![
+                graphics::Effect::Bloom {
+                    threshold: graphics::Param { value: 0.6, beat: -0.2 },
+                    radius: graphics::Param { value: 12., beat: 0. },
+                    intensity: graphics::Param { value: 0.8, beat: 0.6 },
+                },
+                graphics::Effect::ChromaticAberration {
+                    offset: graphics::Param { value: 0.001, beat: 0.004 },
+                },
+                graphics::Effect::Vignette {
+                    radius: graphics::Param { value: 0.6, beat: 0. },
+                    strength: graphics::Param { value: 0.5, beat: 0. },
+                },
+            ],
+            art: graphics::ArtSettings {
+                size: 0.3,
+                opacity: 0.9,
+                margin: 12,
+            },
+            art_theme: true,
+            grid: graphics::GridSettings {
+                frequencies: Some(graphics::FrequencyGrid::Octave),
+                db_step: Some(12.),
+                opacity: 0.15,
+            },
+            text_size: 14.,
+            hud: true,
+        },
+        graphics::Layout::new(
+            graphics::Node::Split(graphics::Direction::Vertical, vec![
+                (3., graphics::Node::Split(graphics::Direction::Horizontal, vec![
+                    (1., graphics::Node::Panel("levels".into())),
+                    (6., graphics::Node::Stack(vec![
+                        graphics::Node::Panel("bars".into()),
+                        graphics::Node::Panel("spectrum".into()),
+                        graphics::Node::Panel("mid_side".into()),
+                        graphics::Node::Panel("radial".into()),
+                        graphics::Node::Panel("art".into()),
+                    ])),
+                    (1., graphics::Node::Panel("peaks".into())),
+                ])),
+                (2., graphics::Node::Split(graphics::Direction::Horizontal, vec![
+                    (2., graphics::Node::Panel("spectrogram".into())),
+                    (2., graphics::Node::Panel("terrain".into())),
+                    (1., graphics::Node::Panel("phase".into())),
+                    (2., graphics::Node::Panel("scope".into())),
+                ])),
+                (0.3, graphics::Node::Panel("correlation".into())),
+            ]),
+            4,
+        ),
+        keymap,
+    );
 
+    let mut app = match app {
+        Ok(app) => app,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        },
+    };
     event_loop.run_app(&mut app).unwrap();
 }
\ No newline at end of file
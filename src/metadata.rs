@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey},
+    probe::Hint,
+};
+
+/// Largest side of the cover art kept in memory, in pixels.
+const ART_SIZE: u32 = 512;
+
+/// Decoded cover art.
+#[derive(Clone, Debug)]
+pub struct Art {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA bytes, from the top row down.
+    pub pixels: Vec<u8>,
+}
+
+impl Art {
+    fn decode(data: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(data)
+            .inspect_err(|error| eprintln!("Cannot decode cover art: {error}"))
+            .ok()?;
+        let image = match image.width().max(image.height()) > ART_SIZE {
+            true => image.thumbnail(ART_SIZE, ART_SIZE),
+            false => image,
+        }.to_rgba8();
+
+        Some(Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        })
+    }
+}
+
+/// Tags and cover art of a track. Anything missing or unreadable is `None`.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub art: Option<Art>,
+}
+
+impl Metadata {
+    /// Reads the tags from MP3, FLAC, OGG and M4A files. Tags stored before
+    /// the audio (as ID3v2 is) and in the container itself are both looked at.
+    pub fn read(path: &Path) -> Self {
+        let mut metadata = Self::default();
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!("Cannot read tags from {}: {error}", path.display());
+                return metadata;
+            },
+        };
+        let source = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let mut probed = match symphonia::default::get_probe().format(&hint, source, &FormatOptions::default(), &MetadataOptions::default()) {
+            Ok(probed) => probed,
+            Err(error) => {
+                eprintln!("Cannot read tags from {}: {error}", path.display());
+                return metadata;
+            },
+        };
+
+        if let Some(mut log) = probed.metadata.get() {
+            if let Some(revision) = log.skip_to_latest() {
+                metadata.fill(revision);
+            }
+        }
+        if let Some(revision) = probed.format.metadata().skip_to_latest() {
+            metadata.fill(revision);
+        }
+
+        metadata
+    }
+
+    /// Takes whatever is still missing from `revision`.
+    fn fill(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+            let value = tag.value.to_string();
+            if field.is_none() && !value.trim().is_empty() {
+                *field = Some(value.trim().to_string());
+            }
+        }
+
+        if self.art.is_none() {
+            // Prefer the front cover, but take any picture over none
+            let visual = revision.visuals().iter()
+                .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+                .or(revision.visuals().first());
+            self.art = visual.and_then(|visual| Art::decode(&visual.data));
+        }
+    }
+
+    /// "Artist - Title", or as much of it as is known, falling back to the
+    /// file name.
+    pub fn display_title(&self, path: &Path) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{artist} - {title}"),
+            (None, Some(title)) => title.clone(),
+            _ => path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Tags of a track being read on a worker thread, so probing the file and
+/// decoding its cover art never holds up a frame.
+pub struct PendingMetadata {
+    path: PathBuf,
+    receiver: Receiver<Metadata>,
+}
+
+impl PendingMetadata {
+    pub fn read(path: &Path) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_path_buf();
+        let worker_path = path.clone();
+        thread::spawn(move || sender.send(Metadata::read(&worker_path)));
+
+        Self { path, receiver }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The tags, once they have been read.
+    pub fn try_take(&self) -> Option<Metadata> {
+        self.receiver.try_recv().ok()
+    }
+}
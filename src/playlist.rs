@@ -0,0 +1,407 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the formats the decoder can play.
+const AUDIO_EXTENSIONS: [&str; 4] = ["mp3", "flac", "ogg", "wav"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    /// Stops after the last track.
+    Off,
+    /// Starts over after the last track.
+    All,
+    /// Plays the current track again and again.
+    One,
+}
+
+impl Repeat {
+    fn next(self) -> Self {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.contains(&extension.to_lowercase().as_str()))
+}
+
+/// Path of a playlist entry, which can be relative to the playlist itself.
+fn resolve(directory: &Path, entry: &str) -> Option<PathBuf> {
+    let entry = entry.strip_prefix("file://").unwrap_or(entry);
+
+    if entry.contains("://") {
+        eprintln!("Skipping {entry}: streams are not supported");
+        return None;
+    }
+    Some(directory.join(entry))
+}
+
+/// Entries of an M3U playlist, skipping comments and `#EXT` directives.
+fn read_m3u(directory: &Path, contents: &str) -> Vec<PathBuf> {
+    contents.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| resolve(directory, line))
+        .collect()
+}
+
+/// Entries of a PLS playlist, in the order of their `FileN` keys.
+fn read_pls(directory: &Path, contents: &str) -> Vec<PathBuf> {
+    let mut entries: Vec<(usize, PathBuf)> = contents.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let number = key.strip_prefix("File")?.parse().ok()?;
+            Some((number, resolve(directory, value.trim())?))
+        })
+        .collect();
+
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Audio files under `directory`, in name order.
+fn scan(directory: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect(),
+        Err(error) => {
+            eprintln!("Cannot read {}: {error}", directory.display());
+            return Vec::new();
+        },
+    };
+    paths.sort();
+
+    paths.into_iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                scan(&path)
+            } else if has_extension(&path, &AUDIO_EXTENSIONS) {
+                vec![path]
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+/// Tracks named by `path`, which can be a playlist, a directory or a track.
+fn expand(path: &Path) -> Vec<PathBuf> {
+    if path.is_dir() {
+        return scan(path);
+    }
+
+    let playlist = if has_extension(path, &["m3u", "m3u8"]) {
+        read_m3u
+    } else if has_extension(path, &["pls"]) {
+        read_pls
+    } else {
+        return vec![path.to_path_buf()];
+    };
+
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            playlist(directory, &contents)
+        },
+        Err(error) => {
+            eprintln!("Cannot read {}: {error}", path.display());
+            Vec::new()
+        },
+    }
+}
+
+/// Tracks in a mix of audio files, M3U and PLS playlists and directories,
+/// which are scanned recursively. Missing files and files in other formats
+/// are skipped.
+fn gather(paths: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    paths.into_iter()
+        .flat_map(|path| expand(&path))
+        .filter(|path| {
+            if !path.is_file() {
+                eprintln!("Skipping {}: no such file", path.display());
+                false
+            } else if !has_extension(path, &AUDIO_EXTENSIONS) {
+                eprintln!("Skipping {}: not an audio file", path.display());
+                false
+            } else {
+                true
+            }
+        })
+        .collect()
+}
+
+/// Tracks to play in order, with the current one. Tracks are referred to by
+/// their index in the list they were given in, whatever the playing order.
+pub struct Playlist {
+    tracks: Vec<PathBuf>,
+    /// Indices into `tracks`, in playing order.
+    order: Vec<usize>,
+    current: usize,
+    shuffle: bool,
+    repeat: Repeat,
+}
+
+impl Playlist {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>, shuffle: bool, repeat: Repeat) -> Self {
+        let tracks = gather(paths);
+
+        let mut playlist = Self {
+            order: (0..tracks.len()).collect(),
+            tracks,
+            current: 0,
+            shuffle: false,
+            repeat,
+        };
+        if shuffle {
+            playlist.toggle_shuffle();
+        }
+        playlist
+    }
+
+    /// Adds tracks to the end, shuffled among themselves if shuffle is on.
+    /// Returns how many were added.
+    pub fn extend(&mut self, paths: impl IntoIterator<Item = PathBuf>) -> usize {
+        let tracks = gather(paths);
+        let added = self.tracks.len()..self.tracks.len() + tracks.len();
+        self.tracks.extend(tracks);
+
+        let start = self.order.len();
+        self.order.extend(added.clone());
+        if self.shuffle {
+            fastrand::shuffle(&mut self.order[start..]);
+        }
+        added.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    pub fn track(&self, index: usize) -> &Path {
+        &self.tracks[index]
+    }
+
+    /// Track played first.
+    pub fn first(&self) -> usize {
+        self.order[0]
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Drops the track at `index`, such as one that cannot be decoded. Tracks
+    /// after it move down one index, and if it was the current track, the
+    /// one after it in playing order becomes current.
+    pub fn remove(&mut self, index: usize) {
+        let Some(place) = self.order.iter().position(|&track| track == index) else {
+            return;
+        };
+        self.tracks.remove(index);
+        self.order.remove(place);
+        for track in &mut self.order {
+            if *track > index {
+                *track -= 1;
+            }
+        }
+
+        if self.current == index {
+            self.current = self.order.get(place).or(self.order.first()).copied().unwrap_or(0);
+        } else if self.current > index {
+            self.current -= 1;
+        }
+    }
+
+    pub fn go_to(&mut self, index: usize) {
+        self.current = index.min(self.tracks.len().saturating_sub(1));
+    }
+
+    /// Track played `offset` places from the current one, wrapping around if
+    /// `wrap` is set.
+    fn step(&self, offset: isize, wrap: bool) -> Option<usize> {
+        let place = self.order.iter().position(|&track| track == self.current)? as isize + offset;
+        let len = self.order.len() as isize;
+
+        match (0..len).contains(&place) {
+            true => Some(self.order[place as usize]),
+            false if wrap => Some(self.order[place.rem_euclid(len) as usize]),
+            false => None,
+        }
+    }
+
+    /// Track that follows the current one when it ends.
+    pub fn following(&self) -> Option<usize> {
+        match self.repeat {
+            Repeat::One => Some(self.current),
+            repeat => self.step(1, repeat == Repeat::All),
+        }
+    }
+
+    /// Track skipped to, which wraps around unless repeat is off.
+    pub fn next(&self) -> Option<usize> {
+        self.step(1, self.repeat != Repeat::Off)
+    }
+
+    /// Track skipped back to, which wraps around unless repeat is off.
+    pub fn previous(&self) -> Option<usize> {
+        self.step(-1, self.repeat != Repeat::Off)
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Shuffles the tracks, starting from the current one, or puts them back
+    /// in order.
+    pub fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+
+        self.order.sort();
+        if self.shuffle && !self.order.is_empty() {
+            self.order.swap(0, self.current);
+            fastrand::shuffle(&mut self.order[1..]);
+        }
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        self.repeat = self.repeat.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Playlist of `count` made-up tracks, skipping the file checks.
+    fn playlist(count: usize, repeat: Repeat) -> Playlist {
+        Playlist {
+            tracks: (0..count).map(|i| PathBuf::from(format!("{i}.mp3"))).collect(),
+            order: (0..count).collect(),
+            current: 0,
+            shuffle: false,
+            repeat,
+        }
+    }
+
+    /// Empty directory of its own for a test.
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("playlist-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn m3u_skips_comments_directives_and_streams() {
+        let contents = "#EXTM3U\n#EXTINF:123,Artist - Title\none.mp3\n\n# a comment\nfile:///music/two.flac\nhttp://radio.example/stream\n  sub/three.ogg  \n";
+
+        assert_eq!(read_m3u(Path::new("/lists"), contents), [
+            PathBuf::from("/lists/one.mp3"),
+            PathBuf::from("/music/two.flac"),
+            PathBuf::from("/lists/sub/three.ogg"),
+        ]);
+    }
+
+    #[test]
+    fn pls_is_ordered_by_file_number() {
+        let contents = "[playlist]\nFile2=two.mp3\nTitle2=Two\nFile10=ten.mp3\nFile1=one.mp3\nNumberOfEntries=3\nVersion=2\n";
+
+        assert_eq!(read_pls(Path::new("/lists"), contents), [
+            PathBuf::from("/lists/one.mp3"),
+            PathBuf::from("/lists/two.mp3"),
+            PathBuf::from("/lists/ten.mp3"),
+        ]);
+    }
+
+    #[test]
+    fn stops_at_the_ends_without_repeat() {
+        let mut playlist = playlist(3, Repeat::Off);
+        assert_eq!(playlist.previous(), None);
+
+        playlist.go_to(2);
+        assert_eq!(playlist.following(), None);
+        assert_eq!(playlist.next(), None);
+        assert_eq!(playlist.previous(), Some(1));
+    }
+
+    #[test]
+    fn wraps_around_with_repeat_all() {
+        let mut playlist = playlist(3, Repeat::All);
+        assert_eq!(playlist.previous(), Some(2));
+
+        playlist.go_to(2);
+        assert_eq!(playlist.following(), Some(0));
+        assert_eq!(playlist.next(), Some(0));
+    }
+
+    #[test]
+    fn repeat_one_follows_with_the_same_track_but_still_skips() {
+        let mut playlist = playlist(3, Repeat::One);
+        playlist.go_to(2);
+
+        assert_eq!(playlist.following(), Some(2));
+        assert_eq!(playlist.next(), Some(0));
+        assert_eq!(playlist.previous(), Some(1));
+    }
+
+    #[test]
+    fn shuffle_starts_from_the_current_track() {
+        let mut playlist = playlist(20, Repeat::Off);
+        playlist.go_to(7);
+
+        playlist.toggle_shuffle();
+        assert_eq!(playlist.order[0], 7);
+        let mut order = playlist.order.clone();
+        order.sort();
+        assert_eq!(order, (0..20).collect::<Vec<_>>());
+
+        playlist.toggle_shuffle();
+        assert_eq!(playlist.order, (0..20).collect::<Vec<_>>());
+        assert_eq!(playlist.current(), 7);
+    }
+
+    #[test]
+    fn extend_adds_audio_files_after_the_existing_tracks() {
+        let directory = directory("extend");
+        for name in ["b.flac", "a.mp3", "cover.jpg", "notes.txt"] {
+            fs::write(directory.join(name), []).unwrap();
+        }
+
+        let mut playlist = playlist(2, Repeat::Off);
+        playlist.toggle_shuffle();
+        let added = playlist.extend([directory.clone(), directory.join("cover.jpg")]);
+
+        assert_eq!(added, 2);
+        assert_eq!(playlist.track(2), directory.join("a.mp3"));
+        assert_eq!(playlist.track(3), directory.join("b.flac"));
+        let mut new = playlist.order[2..].to_vec();
+        new.sort();
+        assert_eq!(new, [2, 3]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn removing_the_current_track_moves_on_to_the_next() {
+        let mut playlist = playlist(4, Repeat::Off);
+        playlist.go_to(1);
+
+        playlist.remove(1);
+        assert_eq!(playlist.order, [0, 1, 2]);
+        assert_eq!(playlist.track(playlist.current()), Path::new("2.mp3"));
+
+        playlist.go_to(2);
+        playlist.remove(2);
+        assert_eq!(playlist.track(playlist.current()), Path::new("0.mp3"));
+    }
+}
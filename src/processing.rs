@@ -1,43 +1,223 @@
-use std::sync::Arc;
+use std::time::Duration;
+
 use itertools::Itertools;
 
-use rustfft::{
-    num_complex::{Complex, Complex32}, Fft, FftPlanner
-};
+use rustfft::num_complex::{Complex, Complex32};
+
+mod bands;
+pub mod beat;
+mod fft;
+pub mod meter;
+pub mod peak;
+pub mod scope;
+pub mod stereo;
+
+use beat::BeatDetector;
+use fft::{FftProcessor, HannWindow, MeanExt};
+pub use fft::FrequencyScale;
+use meter::{Meter, MeterType};
+use peak::{PeakLevels, PeakMeter, PeakSettings};
+use scope::{Scope, ScopeSettings};
+use stereo::StereoMeter;
+
+#[derive(Default, Copy, Clone, Debug)]
+pub struct PhaseVertex {
+    pub xy: [f32; 2],
+    pub hsl: [f32; 3],
+}
 
 pub struct ProcessorOutput {
     pub left_fft: Vec<f32>,
     pub right_fft: Vec<f32>,
+    /// Empty unless the processor was created with mid/side enabled.
+    pub mid_fft: Vec<f32>,
+    pub side_fft: Vec<f32>,
+    pub phase_left: Vec<PhaseVertex>,
+    pub left_peak: PeakLevels,
+    pub right_peak: PeakLevels,
+    pub left_level: f32,
+    pub right_level: f32,
+    pub correlation: f32,
+    pub balance: f32,
+    /// Jumps to 1 on each detected beat and decays towards 0.
+    pub beat: f32,
+    /// Position between beats, from 0 on a beat towards 1 at the next.
+    pub beat_phase: f32,
+    /// Bass, mid and treble levels in 0..1.
+    pub bands: [f32; 3],
+    /// Newest `WAVEFORM_LEN` samples of the mono mix, in -1..1.
+    pub waveform: Vec<f32>,
+    /// Triggered left and right windows in -1..1, empty without a scope.
+    pub scope: (Vec<f32>, Vec<f32>),
+}
+
+/// Length of `ProcessorOutput::waveform`.
+pub const WAVEFORM_LEN: usize = 512;
+
+/// Coefficient of a one-pole smoother that covers 63% of a step in
+/// `time_constant` seconds.
+pub(crate) fn one_pole(time_constant: f32, sample_rate: usize) -> f32 {
+    1. - (-1. / (time_constant * sample_rate as f32)).exp()
 }
 
 pub struct Processor {
     pub audio_buffer: (Vec<i16>, Vec<i16>),
+    mid_side_buffer: Option<(Vec<i16>, Vec<i16>)>,
+    /// Every sample pair received since the last call to `process_samples`,
+    /// so meters see transients that fall outside of `audio_buffer`.
+    pub new_samples: (Vec<i16>, Vec<i16>),
     fft_window: usize,
     fft_processor: FftProcessor,
     pub fft_output_bins: usize,
     fft_io_vec: Vec<Complex32>,
     window: HannWindow,
+    pub phase_pts: usize,
+    sample_rate: usize,
+    left_peak: PeakMeter,
+    right_peak: PeakMeter,
+    left_meter: Meter,
+    right_meter: Meter,
+    stereo: StereoMeter,
+    beat: BeatDetector,
+    scope: Option<Scope>,
+
+    // Kept to rebuild the meters for another sample rate
+    peak_settings: PeakSettings,
+    meter_type: MeterType,
+    correlation_time: Duration,
+    scope_settings: Option<ScopeSettings>,
 }
 
-pub enum Channel {LEFT, RIGHT}
+pub enum Channel {LEFT, RIGHT, MID, SIDE}
 
 impl Processor {
-    pub fn new(sample_window: usize, fft_output_bins: usize) -> Self{
+    pub fn new(
+        sample_window: usize,
+        fft_output_bins: usize,
+        phase_pts: usize,
+        sample_rate: usize,
+        peak_settings: PeakSettings,
+        meter_type: MeterType,
+        correlation_time: Duration,
+        mid_side: bool,
+        scope_settings: Option<ScopeSettings>,
+    ) -> Self {
         Self {
             audio_buffer: (vec![0; sample_window], vec![0; sample_window]),
+            mid_side_buffer: mid_side.then(|| (vec![0; sample_window], vec![0; sample_window])),
+            new_samples: (Vec::with_capacity(sample_window), Vec::with_capacity(sample_window)),
             fft_window: sample_window,
-            fft_processor: FftProcessor::new(sample_window),
+            fft_processor: FftProcessor::new(sample_window, false),
             window: HannWindow::new(sample_window),
             fft_output_bins,
             fft_io_vec: vec![Complex32::ZERO; sample_window * 2],
+            phase_pts,
+            sample_rate,
+            left_peak: PeakMeter::new(peak_settings.clone()),
+            right_peak: PeakMeter::new(peak_settings.clone()),
+            left_meter: Meter::new(meter_type, sample_rate),
+            right_meter: Meter::new(meter_type, sample_rate),
+            stereo: StereoMeter::new(correlation_time, sample_rate),
+            beat: BeatDetector::new(sample_rate),
+            scope: scope_settings.map(|settings| Scope::new(settings, sample_rate, sample_window)),
+
+            peak_settings,
+            meter_type,
+            correlation_time,
+            scope_settings,
         }
     }
 
+    /// Starts over from silence, for audio at `sample_rate`.
+    pub fn reset(&mut self, sample_rate: usize) {
+        *self = Self::new(
+            self.fft_window,
+            self.fft_output_bins,
+            self.phase_pts,
+            sample_rate,
+            self.peak_settings.clone(),
+            self.meter_type,
+            self.correlation_time,
+            self.mid_side_buffer.is_some(),
+            self.scope_settings,
+        );
+    }
+
     pub fn process_samples(&mut self) -> ProcessorOutput {
         let left_fft = self.process_fft_samples(Channel::LEFT);
         let right_fft = self.process_fft_samples(Channel::RIGHT);
 
-        ProcessorOutput { left_fft, right_fft }
+        let (mid_fft, side_fft) = if self.mid_side_buffer.is_some() {
+            self.matrix_mid_side();
+            (self.process_fft_samples(Channel::MID), self.process_fft_samples(Channel::SIDE))
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        let phase_left = self.process_phase_samples();
+        let bands = bands::band_levels(&left_fft, &right_fft, self.bin_width(), 32768. * self.fft_window as f32 / 2.);
+        let waveform = self.process_waveform();
+        let scope = self.scope.as_ref()
+            .map(|scope| scope.process(&self.audio_buffer.0, &self.audio_buffer.1))
+            .unwrap_or_default();
+
+        let left_peak = self.left_peak.process(&self.new_samples.0, self.sample_rate);
+        let right_peak = self.right_peak.process(&self.new_samples.1, self.sample_rate);
+        let left_level = self.left_meter.process(&self.new_samples.0);
+        let right_level = self.right_meter.process(&self.new_samples.1);
+        let (correlation, balance) = self.stereo.process(&self.new_samples.0, &self.new_samples.1);
+        let beat = self.beat.process(&self.new_samples.0, &self.new_samples.1);
+        let beat_phase = self.beat.phase();
+        self.new_samples.0.clear();
+        self.new_samples.1.clear();
+
+        ProcessorOutput {
+            left_fft,
+            right_fft,
+            mid_fft,
+            side_fft,
+            phase_left,
+            left_peak,
+            right_peak,
+            left_level,
+            right_level,
+            correlation,
+            balance,
+            beat,
+            beat_phase,
+            bands,
+            waveform,
+            scope,
+        }
+    }
+
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Length of the analysis window, which sets the magnitude of a full
+    /// scale sine in the FFT output.
+    pub fn fft_window(&self) -> usize {
+        self.fft_window
+    }
+
+    pub fn mid_side(&self) -> bool {
+        self.mid_side_buffer.is_some()
+    }
+
+    /// Samples per scope window, or `None` without a scope.
+    pub fn scope_window(&self) -> Option<usize> {
+        self.scope.as_ref().map(Scope::window)
+    }
+
+    pub fn scope_settings(&self) -> Option<ScopeSettings> {
+        self.scope_settings
+    }
+
+    /// Width of each output bin in Hz.
+    pub fn bin_width(&self) -> f32 {
+        let chunk = self.fft_window / self.fft_output_bins;
+        chunk as f32 * self.sample_rate as f32 / (2 * self.fft_window) as f32
     }
 
     fn process_fft_samples(&mut self, source: Channel) -> Vec<f32> {
@@ -46,16 +226,18 @@ impl Processor {
         let channel = match source {
             Channel::LEFT => &self.audio_buffer.0,
             Channel::RIGHT => &self.audio_buffer.1,
+            Channel::MID => &self.mid_side_buffer.as_ref().unwrap().0,
+            Channel::SIDE => &self.mid_side_buffer.as_ref().unwrap().1,
         };
 
         let samples_complex = channel.iter()
-            .enumerate() 
+            .enumerate()
             .map( |(u, d)| self.window.process((u, *d)))// Apply windowing
             .map( |x| Complex::new(x, 0.)) // Convert to Complex
             .chain(std::iter::repeat_n(Complex::ZERO, self.fft_window)) // Pad zeros
-            .collect_into(&mut self.fft_io_vec); // Collect into vec 
+            .collect_into(&mut self.fft_io_vec); // Collect into vec
 
-        self.fft_processor.process_batch(samples_complex); 
+        self.fft_processor.process_batch(samples_complex);
 
         let parsed_samples = samples_complex
             .iter()
@@ -63,110 +245,74 @@ impl Processor {
             .take(self.fft_window)  // Drop half the values
             .chunks(self.fft_window / self.fft_output_bins)// Average the value into bins
             .into_iter()
-            .map(|chunk| 
+            .map(|chunk|
                 chunk.mean::<f32>()) // Take the mean
             .take(self.fft_output_bins)
             .collect();
 
         parsed_samples
     }
-}
-
-struct HannWindow {
-    scales: Vec<f32>
-}
-
-impl HannWindow {
-    fn new(window_size: usize) -> Self {
-        use std::f32::consts::PI;
-
-        let scales = (0..window_size)
-            .map(|i| 
-            (PI * i as f32 / window_size as f32).sin().powi(2)
-            )
-            .collect();
-
-        Self { scales }
-    }
 
-    fn process(&self, sample: (usize, i16)) -> f32 {
-        let (i, sample) = sample;
-        return self.scales.get(i).unwrap() * sample as f32;
-    }
-}
-
-struct FftProcessor {
-    fft: Arc<dyn Fft<f32>>,
-    scratch: Vec<Complex32>,
-}
+    /// Converts the left/right window into mid (L+R)/2 and side (L-R)/2.
+    fn matrix_mid_side(&mut self) {
+        let (left, right) = &self.audio_buffer;
+        let (mid, side) = self.mid_side_buffer.as_mut().unwrap();
 
-impl FftProcessor {
-    fn new(sample_window: usize) -> Self {
-        let mut planner = FftPlanner::<f32>::new();
-        let fft = planner.plan_fft_forward(sample_window * 2);
-
-        Self {
-            fft,
-            scratch: vec![Complex32::ZERO; sample_window * 2]
+        for (i, (&l, &r)) in left.iter().zip(right).enumerate() {
+            let (l, r) = (l as i32, r as i32);
+            mid[i] = ((l + r) / 2) as i16;
+            side[i] = ((l - r) / 2) as i16;
         }
     }
 
-    fn process_batch(&mut self, input: &mut [Complex32]) {
-        self.fft.process_with_scratch(input, &mut self.scratch);
-    }
-}
-
-// struct PhaseSpaceProcessor {
-//     history: HeapRb<f32>
-
+    fn process_waveform(&self) -> Vec<f32> {
+        let (left, right) = &self.audio_buffer;
+        let start = left.len().saturating_sub(WAVEFORM_LEN);
 
-// }
-
-// Code from https://stackoverflow.com/questions/43921436/extend-iterator-with-a-mean-method
-trait MeanExt: Iterator {
-    fn mean<M>(self) -> M
-    where
-        M: Mean<Self::Item>,
-        Self: Sized,
-    {
-        M::mean(self)
+        left[start..].iter()
+            .zip(&right[start..])
+            .map(|(&l, &r)| (l as f32 + r as f32) / 65536.)
+            .collect()
     }
-}
-
-impl<I: Iterator> MeanExt for I {}
 
-trait Mean<A = Self> {
-    fn mean<I>(iter: I) -> Self
-    where
-        I: Iterator<Item = A>;
-}
-
-impl Mean for f32 {
-    fn mean<I>(iter: I) -> Self
-    where
-        I: Iterator<Item = f32>,
-    {
-        let mut sum = 0.0;
-        let mut count: usize = 0;
-
-        for v in iter {
-            sum += v;
-            count += 1;
-        }
-
-        if count > 0 {
-            sum / (count as f32)
-        } else {
-            0.0
-        }
+    fn process_phase_samples(&self) -> Vec<PhaseVertex> {
+        let (left, right) = &self.audio_buffer;
+
+        left[left.len() - self.phase_pts..].iter()
+            .zip(&right[right.len() - self.phase_pts..])
+            .map(|(&l, &r)| {
+                let (l, r) = (l as f32, r as f32);
+                PhaseVertex {
+                    xy: [l, r],
+                    hsl: [(l - r) / 2., (l + r) / 2., l.abs().max(r.abs())],
+                }
+            })
+            .collect()
     }
 }
 
-impl<'a> Mean<&'a f32> for f32 {
-    fn mean<I>(iter: I) -> Self
-    where
-        I: Iterator<Item = &'a f32>,
-    {
-        iter.copied().mean()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_window_follows_the_sample_rate() {
+        let scope = ScopeSettings { timebase: Duration::from_millis(20), trigger: None };
+        let mut processor = Processor::new(
+            4096, 1024, 512, 48000,
+            PeakSettings::new(Duration::from_secs(1), Duration::from_secs(2)),
+            MeterType::Vu, Duration::from_millis(300), false, Some(scope),
+        );
+        assert_eq!(processor.scope_window(), Some(960));
+
+        processor.reset(96000);
+        assert_eq!(processor.scope_window(), Some(1920));
+
+        processor.reset(22050);
+        assert_eq!(processor.scope_window(), Some(441));
+
+        // Never longer than the audio buffer it is cut from
+        processor.reset(384000);
+        assert_eq!(processor.scope_window(), Some(4096));
     }
 }
@@ -0,0 +1,27 @@
+/// Upper edges of the bass and mid bands in Hz. Treble is everything above.
+const BASS_TOP: f32 = 250.;
+const MID_TOP: f32 = 4000.;
+/// Level that reads as 0, relative to a full scale sine.
+const MIN_DB: f32 = -60.;
+
+/// Bass, mid and treble levels from a pair of spectra, each mapped from
+/// `MIN_DB`..0 dB onto 0..1.
+pub fn band_levels(left: &[f32], right: &[f32], bin_width: f32, reference: f32) -> [f32; 3] {
+    let edges = [0., BASS_TOP, MID_TOP, f32::INFINITY];
+    let mut power = [0f32; 3];
+    let mut count = [0usize; 3];
+
+    for (i, (&l, &r)) in left.iter().zip(right).enumerate().skip(1) {
+        let frequency = i as f32 * bin_width;
+        let band = edges.windows(2).position(|edge| frequency < edge[1]).unwrap();
+        let magnitude = (l + r) / 2. / reference;
+        power[band] += magnitude * magnitude;
+        count[band] += 1;
+    }
+
+    std::array::from_fn(|band| {
+        let rms = (power[band] / count[band].max(1) as f32).sqrt();
+        let db = 20. * rms.max(f32::MIN_POSITIVE).log10();
+        (1. - db / MIN_DB).clamp(0., 1.)
+    })
+}
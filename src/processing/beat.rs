@@ -0,0 +1,98 @@
+use super::one_pole;
+
+/// Cut-off of the low pass filter in front of the detector.
+const BASS_CUTOFF: f32 = 150.;
+/// Time constants of the short and long term energy averages.
+const SHORT_TIME: f32 = 0.05;
+const LONG_TIME: f32 = 1.5;
+/// How far short term energy has to rise above the long term average.
+const THRESHOLD: f32 = 1.5;
+/// Minimum time between two beats.
+const REFRACTORY: f32 = 0.25;
+/// Decay time constant of the output pulse.
+const PULSE_TIME: f32 = 0.15;
+/// Longest gap between beats still taken as the tempo.
+const MAX_PERIOD: f32 = 2.;
+/// Weight of each new interval in the tempo average.
+const PERIOD_SMOOTHING: f32 = 0.2;
+
+/// Detects bass onsets by comparing short and long term energy of the
+/// low-passed signal. Runs per sample so it does not depend on frame rate.
+pub struct BeatDetector {
+    sample_rate: usize,
+    lowpass_coef: f32,
+    short_coef: f32,
+    long_coef: f32,
+    pulse_decay: f32,
+
+    lowpass: f32,
+    short: f32,
+    long: f32,
+    since_beat: usize,
+    pulse: f32,
+    /// Average time between beats in samples, 0 until two beats are seen.
+    period: f32,
+}
+
+impl BeatDetector {
+    pub fn new(sample_rate: usize) -> Self {
+        Self {
+            sample_rate,
+            lowpass_coef: 1. - (-2. * std::f32::consts::PI * BASS_CUTOFF / sample_rate as f32).exp(),
+            short_coef: one_pole(SHORT_TIME, sample_rate),
+            long_coef: one_pole(LONG_TIME, sample_rate),
+            pulse_decay: 1. - one_pole(PULSE_TIME, sample_rate),
+
+            lowpass: 0.,
+            short: 0.,
+            long: 0.,
+            since_beat: usize::MAX / 2,
+            pulse: 0.,
+            period: 0.,
+        }
+    }
+
+    /// Feeds a block of mono samples and returns the beat pulse, which jumps
+    /// to 1 on every detected beat and decays exponentially towards 0.
+    pub fn process(&mut self, left: &[i16], right: &[i16]) -> f32 {
+        let refractory = (REFRACTORY * self.sample_rate as f32) as usize;
+
+        for (&l, &r) in left.iter().zip(right) {
+            let x = (l as f32 + r as f32) / 65536.;
+
+            self.lowpass += self.lowpass_coef * (x - self.lowpass);
+            let energy = self.lowpass * self.lowpass;
+            self.short += self.short_coef * (energy - self.short);
+            self.long += self.long_coef * (energy - self.long);
+
+            self.pulse *= self.pulse_decay;
+            self.since_beat += 1;
+
+            if self.since_beat > refractory && self.short > self.long * THRESHOLD && self.short > 1e-6 {
+                let interval = self.since_beat as f32;
+                if interval < MAX_PERIOD * self.sample_rate as f32 {
+                    self.period = if self.period == 0. {
+                        interval
+                    } else {
+                        self.period + PERIOD_SMOOTHING * (interval - self.period)
+                    };
+                }
+                self.since_beat = 0;
+                self.pulse = 1.;
+            }
+        }
+
+        self.pulse
+    }
+
+    /// Position within the current beat, running from 0 on a beat towards 1
+    /// at the next expected one. Keeps cycling at the last tempo between
+    /// detections.
+    pub fn phase(&self) -> f32 {
+        if self.period == 0. {
+            0.
+        } else {
+            (self.since_beat as f32 / self.period).fract()
+        }
+    }
+}
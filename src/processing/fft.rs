@@ -113,7 +113,7 @@ pub struct Mel(f32);
 impl From<f32> for Mel {
     fn from(value: f32) -> Self {
         // From http://practicalcryptography.com/miscellaneous/machine-learning/guide-mel-frequency-cepstral-coefficients-mfccs/#eqn1
-        Mel(1125. * (1. + value / 700.))
+        Mel(1125. * (1. + value / 700.).ln())
     }
 }
 
@@ -139,6 +139,28 @@ impl std::ops::Mul<f32> for Mel {
     }
 }
 
+/// Lowest frequency shown on logarithmic scales.
+pub const LOG_MIN_FREQUENCY: f32 = 20.;
+
+/// How frequencies are spread along a display axis.
+#[derive(Clone, Copy, Debug)]
+pub enum FrequencyScale {
+    Linear,
+    Log,
+    Mel,
+}
+
+impl FrequencyScale {
+    /// Frequency at `position` along the axis, from 0 at DC to 1 at Nyquist.
+    pub fn frequency(&self, position: f32, nyquist: f32) -> f32 {
+        match self {
+            FrequencyScale::Linear => position * nyquist,
+            FrequencyScale::Log => LOG_MIN_FREQUENCY * (nyquist / LOG_MIN_FREQUENCY).powf(position),
+            FrequencyScale::Mel => (Mel::from(nyquist) * position).into(),
+        }
+    }
+}
+
 pub struct FftProcessor {
     fft: Arc<dyn Fft<f32>>,
     scratch: Vec<Complex32>,
@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use super::one_pole;
+use super::peak::{to_db, FLOOR_DB};
+
+/// Rectified-average reading of a sine relative to its RMS value,
+/// so VU meters read the same as RMS meters for tones.
+const SINE_FORM_FACTOR: f32 = std::f32::consts::PI / (2. * std::f32::consts::SQRT_2);
+
+#[derive(Clone, Copy, Debug)]
+pub enum MeterType {
+    /// Rectified average, critically damped to reach 99% in 300 ms.
+    Vu,
+    /// DIN 45406: a 10 ms burst reads 1 dB low, falls 20 dB in 1.5 s.
+    PpmType1,
+    /// IEC 60268-10 IIa (BBC): a 10 ms burst reads 4 dB low, falls 24 dB in 2.8 s.
+    PpmType2,
+    /// Exponentially weighted mean square over the given time constant.
+    Rms(Duration),
+}
+
+enum Detector {
+    Average { coef: f32, stage1: f32, stage2: f32 },
+    Peak { attack: f32, release: f32, envelope: f32 },
+    Rms { coef: f32, mean_square: f32 },
+}
+
+/// Level meter with classic ballistics. All time constants are applied per
+/// sample, so the reading does not depend on how often it is polled.
+pub struct Meter {
+    detector: Detector,
+}
+
+fn fall_back(db: f32, time: f32, sample_rate: usize) -> f32 {
+    10f32.powf(-db / time / 20. / sample_rate as f32)
+}
+
+impl Meter {
+    pub fn new(kind: MeterType, sample_rate: usize) -> Self {
+        let detector = match kind {
+            // Two cascaded poles reach 99% after 6.64 time constants
+            MeterType::Vu => Detector::Average {
+                coef: one_pole(0.3 / 6.64, sample_rate),
+                stage1: 0.,
+                stage2: 0.,
+            },
+            MeterType::PpmType1 => Detector::Peak {
+                attack: one_pole(0.0045, sample_rate),
+                release: fall_back(20., 1.5, sample_rate),
+                envelope: 0.,
+            },
+            MeterType::PpmType2 => Detector::Peak {
+                attack: one_pole(0.01, sample_rate),
+                release: fall_back(24., 2.8, sample_rate),
+                envelope: 0.,
+            },
+            MeterType::Rms(integration) => Detector::Rms {
+                coef: one_pole(integration.as_secs_f32(), sample_rate),
+                mean_square: 0.,
+            },
+        };
+
+        Self { detector }
+    }
+
+    /// Feeds a block of consecutive samples and returns the reading in dBFS.
+    pub fn process(&mut self, samples: &[i16]) -> f32 {
+        let samples = samples.iter().map(|&s| s as f32 / 32768.);
+
+        match &mut self.detector {
+            Detector::Average { coef, stage1, stage2 } => {
+                for x in samples {
+                    *stage1 += *coef * (x.abs() - *stage1);
+                    *stage2 += *coef * (*stage1 - *stage2);
+                }
+                to_db(*stage2 * SINE_FORM_FACTOR)
+            },
+            Detector::Peak { attack, release, envelope } => {
+                for x in samples {
+                    let x = x.abs();
+                    if x > *envelope {
+                        *envelope += *attack * (x - *envelope);
+                    } else {
+                        *envelope *= *release;
+                    }
+                }
+                to_db(*envelope)
+            },
+            Detector::Rms { coef, mean_square } => {
+                for x in samples {
+                    *mean_square += *coef * (x * x - *mean_square);
+                }
+                if *mean_square > 0. {
+                    (10. * mean_square.log10()).max(FLOOR_DB)
+                } else {
+                    FLOOR_DB
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: usize = 48000;
+
+    fn constant(level: i16, time: f32) -> Vec<i16> {
+        vec![level; (time * SAMPLE_RATE as f32) as usize]
+    }
+
+    #[test]
+    fn reading_does_not_depend_on_block_size() {
+        let samples = (0..SAMPLE_RATE)
+            .map(|n| if n < SAMPLE_RATE / 3 { (8000. * (n as f32 * 0.05).sin()) as i16 } else { 0 })
+            .collect::<Vec<_>>();
+
+        for kind in [MeterType::Vu, MeterType::PpmType1, MeterType::PpmType2, MeterType::Rms(Duration::from_millis(300))] {
+            let whole = Meter::new(kind, SAMPLE_RATE).process(&samples);
+
+            let mut meter = Meter::new(kind, SAMPLE_RATE);
+            let mut blocks = 0.;
+            for block in samples.chunks(37) {
+                blocks = meter.process(block);
+            }
+
+            assert_eq!(whole, blocks, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn vu_step_reaches_99_percent_in_300_ms() {
+        let mut meter = Meter::new(MeterType::Vu, SAMPLE_RATE);
+        let early = meter.process(&constant(16384, 0.25));
+        let on_time = meter.process(&constant(16384, 0.05));
+        let settled = meter.process(&constant(16384, 3.));
+
+        assert!(10f32.powf((early - settled) / 20.) < 0.985);
+        assert!((10f32.powf((on_time - settled) / 20.) - 0.99).abs() < 0.002);
+    }
+
+    #[test]
+    fn ppm_falls_back_at_the_standard_rates() {
+        for (kind, db, time) in [(MeterType::PpmType1, 20., 1.5), (MeterType::PpmType2, 24., 2.8)] {
+            let mut meter = Meter::new(kind, SAMPLE_RATE);
+            let held = meter.process(&constant(16384, 1.));
+            let fallen = meter.process(&constant(0, time));
+
+            assert!((held - fallen - db).abs() < 0.1, "{kind:?}: {held} to {fallen}");
+        }
+    }
+}
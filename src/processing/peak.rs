@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+/// Lowest level reported by the meters, in dBFS.
+pub const FLOOR_DB: f32 = -120.;
+
+const OVERSAMPLING: usize = 4;
+const TAPS: usize = 12;
+
+// Polyphase interpolation filter from ITU-R BS.1770-4, Annex 2
+#[allow(clippy::excessive_precision)]
+const TRUE_PEAK_PHASES: [[f32; TAPS]; OVERSAMPLING] = [
+    [
+        0.001708984375, 0.010986328125, -0.0196533203125, 0.033203125,
+        -0.0594482421875, 0.1373291015625, 0.97216796875, -0.102294921875,
+        0.047607421875, -0.026611328125, 0.014892578125, -0.00830078125,
+    ],
+    [
+        -0.0291748046875, 0.029296875, -0.0517578125, 0.089111328125,
+        -0.16650390625, 0.465087890625, 0.77978515625, -0.2003173828125,
+        0.1015625, -0.0582275390625, 0.0330810546875, -0.0189208984375,
+    ],
+    [
+        -0.0189208984375, 0.0330810546875, -0.0582275390625, 0.1015625,
+        -0.2003173828125, 0.77978515625, 0.465087890625, -0.16650390625,
+        0.089111328125, -0.0517578125, 0.029296875, -0.0291748046875,
+    ],
+    [
+        -0.00830078125, 0.014892578125, -0.026611328125, 0.047607421875,
+        -0.102294921875, 0.97216796875, 0.1373291015625, -0.0594482421875,
+        0.033203125, -0.0196533203125, 0.010986328125, 0.001708984375,
+    ],
+];
+
+#[derive(Clone, Debug)]
+pub struct PeakSettings {
+    /// How long the peak marker stays put before falling back.
+    hold: Duration,
+    /// Time taken to fall back by 20 dB.
+    fall: Duration,
+}
+
+impl PeakSettings {
+    pub fn new(hold: Duration, fall: Duration) -> Self {
+        Self { hold, fall }
+    }
+
+    fn fall_rate(&self) -> f32 {
+        20. / self.fall.as_secs_f32()
+    }
+}
+
+/// Meter readings in dBFS.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct PeakLevels {
+    pub sample: f32,
+    pub sample_hold: f32,
+    pub true_peak: f32,
+    pub true_hold: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Ballistics {
+    level: f32,
+    held: f32,
+    hold_left: f32,
+}
+
+impl Ballistics {
+    fn new() -> Self {
+        Self { level: FLOOR_DB, held: FLOOR_DB, hold_left: 0. }
+    }
+
+    fn update(&mut self, peak: f32, dt: f32, settings: &PeakSettings) {
+        let fall = settings.fall_rate() * dt;
+
+        self.level = peak.max(self.level - fall);
+
+        if peak >= self.held {
+            self.held = peak;
+            self.hold_left = settings.hold.as_secs_f32();
+        } else if self.hold_left > 0. {
+            self.hold_left -= dt;
+        } else {
+            self.held = peak.max(self.held - fall);
+        }
+    }
+}
+
+pub struct PeakMeter {
+    settings: PeakSettings,
+    history: [f32; TAPS],
+    head: usize,
+    sample: Ballistics,
+    true_peak: Ballistics,
+}
+
+impl PeakMeter {
+    pub fn new(settings: PeakSettings) -> Self {
+        Self {
+            settings,
+            history: [0.; TAPS],
+            head: 0,
+            sample: Ballistics::new(),
+            true_peak: Ballistics::new(),
+        }
+    }
+
+    /// Runs the meter over a block of consecutive samples and returns the
+    /// readings at the end of the block.
+    pub fn process(&mut self, samples: &[i16], sample_rate: usize) -> PeakLevels {
+        let mut sample_peak = 0f32;
+        let mut true_peak = 0f32;
+
+        for &s in samples {
+            let x = s as f32 / 32768.;
+            sample_peak = sample_peak.max(x.abs());
+
+            self.head = (self.head + 1) % TAPS;
+            self.history[self.head] = x;
+
+            for phase in TRUE_PEAK_PHASES.iter() {
+                let y: f32 = phase.iter()
+                    .enumerate()
+                    .map(|(k, c)| c * self.history[(self.head + TAPS - k) % TAPS])
+                    .sum();
+                true_peak = true_peak.max(y.abs());
+            }
+        }
+
+        let dt = samples.len() as f32 / sample_rate as f32;
+        self.sample.update(to_db(sample_peak), dt, &self.settings);
+        self.true_peak.update(to_db(true_peak.max(sample_peak)), dt, &self.settings);
+
+        PeakLevels {
+            sample: self.sample.level,
+            sample_hold: self.sample.held,
+            true_peak: self.true_peak.level,
+            true_hold: self.true_peak.held,
+        }
+    }
+}
+
+pub fn to_db(amplitude: f32) -> f32 {
+    (20. * amplitude.log10()).max(FLOOR_DB)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use super::*;
+
+    fn settings() -> PeakSettings {
+        PeakSettings::new(Duration::from_millis(500), Duration::from_secs(1))
+    }
+
+    #[test]
+    fn true_peak_finds_the_peak_between_samples() {
+        // A quarter of the sample rate at 45° only ever samples 1/√2 of the
+        // amplitude, which reads 3 dB low
+        let samples = (0..4800)
+            .map(|n| (16384. * (FRAC_PI_2 * n as f32 + FRAC_PI_4).sin()) as i16)
+            .collect::<Vec<_>>();
+
+        let levels = PeakMeter::new(settings()).process(&samples, 48000);
+
+        assert!((levels.sample - to_db(0.5 / 2f32.sqrt())).abs() < 0.01);
+        assert!((levels.true_peak - levels.sample - 3.01).abs() < 0.2, "{levels:?}");
+    }
+
+    #[test]
+    fn peak_holds_then_falls_back() {
+        let settings = settings();
+        let mut ballistics = Ballistics::new();
+        ballistics.update(-6., 0.125, &settings);
+        assert_eq!((ballistics.level, ballistics.held), (-6., -6.));
+
+        // The level falls straight away at 20 dB a second, while the marker
+        // holds for half a second
+        for step in 1..=4 {
+            ballistics.update(FLOOR_DB, 0.125, &settings);
+            assert_eq!(ballistics.level, -6. - 2.5 * step as f32);
+            assert_eq!(ballistics.held, -6.);
+        }
+
+        ballistics.update(FLOOR_DB, 0.125, &settings);
+        assert_eq!(ballistics.held, -8.5);
+
+        // A new peak takes over the marker and restarts the hold
+        ballistics.update(-3., 0.125, &settings);
+        assert_eq!((ballistics.level, ballistics.held), (-3., -3.));
+    }
+}
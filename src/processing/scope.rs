@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+/// Rising-edge trigger. Levels are fractions of full scale.
+#[derive(Clone, Copy, Debug)]
+pub struct Trigger {
+    pub level: f32,
+    /// How far the signal has to fall below `level` before the trigger
+    /// re-arms, so noise around the level does not fire it repeatedly.
+    pub hysteresis: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScopeSettings {
+    /// Time shown across the screen, at most the length of the audio buffer.
+    pub timebase: Duration,
+    /// Free runs on the newest samples when `None`.
+    pub trigger: Option<Trigger>,
+}
+
+impl ScopeSettings {
+    /// Samples per window at `sample_rate`, cut from a buffer of `buffer_len`.
+    pub fn window(&self, sample_rate: usize, buffer_len: usize) -> usize {
+        let window = (self.timebase.as_secs_f32() * sample_rate as f32) as usize;
+        window.clamp(2, buffer_len)
+    }
+}
+
+/// Cuts a triggered window out of the audio buffer, so periodic waveforms
+/// stand still from frame to frame.
+pub struct Scope {
+    settings: ScopeSettings,
+    window: usize,
+}
+
+impl Scope {
+    pub fn new(settings: ScopeSettings, sample_rate: usize, buffer_len: usize) -> Self {
+        Self {
+            settings,
+            window: settings.window(sample_rate, buffer_len),
+        }
+    }
+
+    /// Number of samples in each output window.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Start of the window following the latest trigger, leaving room for a
+    /// full window after it. Falls back to the newest samples if the signal
+    /// never crosses the trigger.
+    fn trigger_point(&self, samples: &[i16]) -> usize {
+        let latest = samples.len() - self.window;
+        let Some(trigger) = self.settings.trigger else {
+            return latest;
+        };
+
+        let level = trigger.level * 32768.;
+        let arm = level - trigger.hysteresis * 32768.;
+
+        let mut armed = false;
+        let mut found = None;
+        for (i, &s) in samples[..=latest].iter().enumerate() {
+            let s = s as f32;
+            if s < arm {
+                armed = true;
+            } else if armed && s >= level {
+                armed = false;
+                found = Some(i);
+            }
+        }
+
+        found.unwrap_or(latest)
+    }
+
+    /// Returns the left and right windows scaled to -1..1, triggered on the
+    /// left channel.
+    pub fn process(&self, left: &[i16], right: &[i16]) -> (Vec<f32>, Vec<f32>) {
+        let start = self.trigger_point(left);
+        let window = |channel: &[i16]| channel[start..start + self.window]
+            .iter()
+            .map(|&s| s as f32 / 32768.)
+            .collect();
+
+        (window(left), window(right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(trigger: Option<Trigger>) -> Scope {
+        let settings = ScopeSettings { timebase: Duration::from_millis(4), trigger };
+        Scope::new(settings, 1000, 16)
+    }
+
+    fn trigger() -> Option<Trigger> {
+        Some(Trigger { level: 0.5, hysteresis: 0.2 })
+    }
+
+    /// Samples from fractions of full scale.
+    fn samples(levels: &[f32]) -> Vec<i16> {
+        levels.iter().map(|&level| (level * 32767.) as i16).collect()
+    }
+
+    #[test]
+    fn triggers_on_the_latest_rising_edge() {
+        let samples = samples(&[
+            0., 0.6, 0.8, 0.6, 0., -0.6, 0., 0.6, 0.8, 0.6, 0., -0.6, 0., 0.6, 0.8, 0.6,
+        ]);
+
+        // The edge at 13 leaves no room for a whole window after it
+        assert_eq!(scope(trigger()).trigger_point(&samples), 7);
+    }
+
+    #[test]
+    fn rearms_only_below_the_hysteresis() {
+        // Dipping to 0.4 stays above the 0.3 needed to re-arm
+        let shallow = samples(&[0., 0.6, 0.4, 0.6, 0.4, 0.6, 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.]);
+        assert_eq!(scope(trigger()).trigger_point(&shallow), 1);
+
+        let deep = samples(&[0., 0.6, 0.2, 0.6, 0.4, 0.6, 0., 0., 0., 0., 0., 0., 0., 0., 0., 0.]);
+        assert_eq!(scope(trigger()).trigger_point(&deep), 3);
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_window() {
+        // Never reaches the level
+        let quiet = samples(&(0..16).map(|i| i as f32 / 40.).collect::<Vec<_>>());
+        assert_eq!(scope(trigger()).trigger_point(&quiet), 12);
+
+        // Never falls far enough to arm
+        let loud = samples(&[0.6; 16]);
+        assert_eq!(scope(trigger()).trigger_point(&loud), 12);
+
+        let rising = samples(&(0..16).map(|i| i as f32 / 16.).collect::<Vec<_>>());
+        assert_eq!(scope(None).trigger_point(&rising), 12);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use super::one_pole;
+
+/// Phase correlation and left/right balance, integrated over a configurable
+/// time constant.
+pub struct StereoMeter {
+    coef: f32,
+    left_right: f32,
+    left_square: f32,
+    right_square: f32,
+}
+
+impl StereoMeter {
+    pub fn new(integration: Duration, sample_rate: usize) -> Self {
+        Self {
+            coef: one_pole(integration.as_secs_f32(), sample_rate),
+            left_right: 0.,
+            left_square: 0.,
+            right_square: 0.,
+        }
+    }
+
+    /// Feeds a block of sample pairs and returns `(correlation, balance)`.
+    ///
+    /// Correlation runs from -1 (out of phase) through 0 (uncorrelated) to
+    /// +1 (mono). Balance runs from -1 (left only) to +1 (right only).
+    pub fn process(&mut self, left: &[i16], right: &[i16]) -> (f32, f32) {
+        for (&l, &r) in left.iter().zip(right) {
+            let (l, r) = (l as f32 / 32768., r as f32 / 32768.);
+            self.left_right += self.coef * (l * r - self.left_right);
+            self.left_square += self.coef * (l * l - self.left_square);
+            self.right_square += self.coef * (r * r - self.right_square);
+        }
+
+        // Power goes as amplitude squared, so this is around FLOOR_DB, and
+        // quiet passages still read a correlation
+        let power = (self.left_square * self.right_square).sqrt();
+        let correlation = if power > 1e-12 {
+            (self.left_right / power).clamp(-1., 1.)
+        } else {
+            0.
+        };
+
+        let (left_rms, right_rms) = (self.left_square.sqrt(), self.right_square.sqrt());
+        let balance = if left_rms + right_rms > f32::EPSILON {
+            (right_rms - left_rms) / (right_rms + left_rms)
+        } else {
+            0.
+        };
+
+        (correlation, balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32) -> Vec<i16> {
+        (0..48000).map(|n| (amplitude * 32767. * (n as f32 * 0.05).sin()) as i16).collect()
+    }
+
+    fn meter() -> StereoMeter {
+        StereoMeter::new(Duration::from_millis(300), 48000)
+    }
+
+    #[test]
+    fn mono_is_fully_correlated() {
+        let samples = sine(0.5);
+        let (correlation, balance) = meter().process(&samples, &samples);
+
+        assert!((correlation - 1.).abs() < 1e-3);
+        assert!(balance.abs() < 1e-3);
+    }
+
+    #[test]
+    fn inverted_is_fully_anticorrelated() {
+        let left = sine(0.5);
+        let right = left.iter().map(|&s| -s).collect::<Vec<_>>();
+        let (correlation, balance) = meter().process(&left, &right);
+
+        assert!((correlation + 1.).abs() < 1e-3);
+        assert!(balance.abs() < 1e-3);
+    }
+
+    #[test]
+    fn left_only_balances_left() {
+        let (correlation, balance) = meter().process(&sine(0.5), &[0; 48000]);
+
+        assert_eq!(correlation, 0.);
+        assert!((balance + 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn quiet_mono_is_still_correlated() {
+        // About -80 dBFS, well below where f32::EPSILON would cut off
+        let samples = sine(1e-4);
+        let (correlation, _) = meter().process(&samples, &samples);
+
+        assert!((correlation - 1.).abs() < 1e-3);
+    }
+}